@@ -0,0 +1,377 @@
+//! pyreq: 不依赖python解析, 检查和格式化requirements文件
+//! FILE为'-'或者省略时从stdin读取
+use std::{
+    fs,
+    io::{self, Read},
+    process::ExitCode,
+};
+
+use pyreq_rs::requirements::{
+    serialization::{StructuredMarker, StructuredRequirement},
+    LineContent, MarkerEnvironment, PipOption, ProjectLocation, Requirement, RequirementsFile,
+    RequirementsFileLine,
+};
+use serde_json::{json, Value};
+
+const USAGE: &str = "usage:
+    pyreq parse [FILE]                  print the syntax tree as JSON
+    pyreq check FILE...                 report syntax errors
+    pyreq fmt [--check] [FILE...]       rewrite files in canonical form
+    pyreq eval [--env NAME=VALUE]... [--extra NAME]... [FILE]
+                                        print requirements whose markers match";
+
+#[derive(Debug, PartialEq)]
+enum Command {
+    Parse(Option<String>),
+    Check(Vec<String>),
+    Fmt {
+        check: bool,
+        files: Vec<String>,
+    },
+    Eval {
+        env: Box<MarkerEnvironment>,
+        extras: Vec<String>,
+        file: Option<String>,
+    },
+}
+
+fn parse_args(args: &[String]) -> Result<Command, String> {
+    let (command, args) = args.split_first().ok_or("missing command")?;
+    match command.as_str() {
+        "parse" => match args {
+            [] => Ok(Command::Parse(None)),
+            [file] => Ok(Command::Parse(Some(file.clone()))),
+            _ => Err("parse accepts at most one file".to_string()),
+        },
+        "check" if args.is_empty() => Err("check requires at least one file".to_string()),
+        "check" => Ok(Command::Check(args.to_vec())),
+        "fmt" => {
+            let check = args.iter().any(|a| a == "--check");
+            let files = args.iter().filter(|a| *a != "--check").cloned().collect();
+            Ok(Command::Fmt { check, files })
+        }
+        "eval" => {
+            let mut env = Box::<MarkerEnvironment>::default();
+            let mut extras = vec![];
+            let mut file = None;
+            let mut args = args.iter();
+            while let Some(arg) = args.next() {
+                // --env NAME=VALUE 或者 --env=NAME=VALUE
+                let (flag, inline) = match arg.split_once('=') {
+                    Some((flag, value)) if flag.starts_with("--") => {
+                        (flag, Some(value.to_string()))
+                    }
+                    _ => (arg.as_str(), None),
+                };
+                match flag {
+                    "--env" | "--extra" => {
+                        let value = inline
+                            .or_else(|| args.next().cloned())
+                            .ok_or(format!("{} requires a value", flag))?;
+                        if flag == "--extra" {
+                            extras.push(value);
+                            continue;
+                        }
+                        let (name, value) = value
+                            .split_once('=')
+                            .ok_or(format!("expected NAME=VALUE, found {:?}", value))?;
+                        *env.get_mut(name)
+                            .ok_or(format!("unknown marker variable {:?}", name))? =
+                            value.to_string();
+                    }
+                    _ if file.is_none() && (arg == "-" || !arg.starts_with('-')) => {
+                        file = Some(arg.clone())
+                    }
+                    _ => return Err(format!("unexpected argument {:?}", arg)),
+                }
+            }
+            Ok(Command::Eval { env, extras, file })
+        }
+        _ => Err(format!("unknown command {:?}", command)),
+    }
+}
+
+fn read(file: Option<&str>) -> Result<String, String> {
+    match file {
+        None | Some("-") => {
+            let mut content = String::new();
+            io::stdin()
+                .read_to_string(&mut content)
+                .map_err(|e| format!("<stdin>: {}", e))?;
+            Ok(content)
+        }
+        Some(path) => fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e)),
+    }
+}
+
+fn parse(file: Option<&str>) -> Result<RequirementsFile, String> {
+    let content = read(file)?;
+    content
+        .parse()
+        .map_err(|e| format!("{}:{}", file.unwrap_or("<stdin>"), e))
+}
+
+fn option_json(option: &PipOption) -> Value {
+    json!({"name": option.name(), "value": option.value()})
+}
+
+fn line_json(line: &RequirementsFileLine) -> Value {
+    let options = |o: &[PipOption]| o.iter().map(option_json).collect::<Vec<_>>();
+    let content = match &line.content {
+        LineContent::Empty => json!({"type": "empty"}),
+        LineContent::Options(o) => json!({"type": "options", "options": options(o)}),
+        LineContent::Requirement(r, o) => json!({
+            "type": "requirement",
+            "requirement": match r {
+                Requirement::Specifier(spec) => json!({
+                    "type": "specifier",
+                    "specifier": StructuredRequirement::from(spec),
+                }),
+                Requirement::ArchiveUrl(url) => json!({"type": "archive_url", "url": url}),
+                Requirement::Project(project) => json!({
+                    "type": "project",
+                    "location": match &project.location {
+                        ProjectLocation::Path(path) => json!({"path": path}),
+                        ProjectLocation::Url(url) => json!({"url": url}),
+                        ProjectLocation::Vcs(vcs) => json!({"vcs": {
+                            "backend": vcs.vcs.to_string(),
+                            "url": vcs.url,
+                            "rev": vcs.rev,
+                            "egg": vcs.egg,
+                            "subdirectory": vcs.subdirectory,
+                            "params": vcs.params,
+                        }}),
+                    },
+                    "extras": project.extras,
+                    "editable": project.editable,
+                    "marker": project.marker_expr.as_ref().map(StructuredMarker::from),
+                }),
+            },
+            "options": options(o),
+        }),
+    };
+    json!({
+        "line_number": line.line_number,
+        "content": content,
+        "comment": line.comment,
+    })
+}
+
+fn file_json(file: &RequirementsFile) -> Value {
+    json!({"lines": file.lines.iter().map(line_json).collect::<Vec<_>>()})
+}
+
+// 没有marker的requirement和archive url总是保留
+fn eval(file: &RequirementsFile, env: &MarkerEnvironment, extras: &[String]) -> Vec<String> {
+    file.lines
+        .iter()
+        .filter_map(|line| match &line.content {
+            content @ LineContent::Requirement(r, _) if r.evaluate(env, extras) => {
+                Some(content.to_string())
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+fn run(command: Command) -> Result<bool, String> {
+    match command {
+        Command::Parse(file) => {
+            let file = parse(file.as_deref())?;
+            println!("{:#}", file_json(&file));
+            Ok(true)
+        }
+        Command::Check(files) => {
+            let mut ok = true;
+            for path in &files {
+                if let Err(e) = parse(Some(path)) {
+                    eprintln!("{}", e);
+                    ok = false;
+                }
+            }
+            Ok(ok)
+        }
+        Command::Fmt { check, files } if files.is_empty() => run(Command::Fmt {
+            check,
+            files: vec!["-".to_string()],
+        }),
+        // 一个文件出错时报告错误, 继续处理其他文件
+        Command::Fmt { check, files } => {
+            let mut ok = true;
+            for path in &files {
+                let content = match read(Some(path)) {
+                    Ok(content) => content,
+                    Err(e) => {
+                        eprintln!("pyreq: {}", e);
+                        ok = false;
+                        continue;
+                    }
+                };
+                let formatted = match content.parse::<RequirementsFile>() {
+                    Ok(file) => file.to_string(),
+                    Err(e) => {
+                        eprintln!("{}:{}", path, e);
+                        ok = false;
+                        continue;
+                    }
+                };
+                let changed = formatted != content;
+                match (path.as_str(), check) {
+                    // --check时stdin只通过退出码报告
+                    ("-", true) => ok &= !changed,
+                    ("-", false) => print!("{}", formatted),
+                    (_, true) if changed => {
+                        eprintln!("would reformat {}", path);
+                        ok = false;
+                    }
+                    (_, false) if changed => {
+                        if let Err(e) = fs::write(path, formatted) {
+                            eprintln!("pyreq: {}: {}", path, e);
+                            ok = false;
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            Ok(ok)
+        }
+        Command::Eval { env, extras, file } => {
+            let file = parse(file.as_deref())?;
+            for line in eval(&file, &env, &extras) {
+                println!("{}", line);
+            }
+            Ok(true)
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let command = match parse_args(&args) {
+        Ok(command) => command,
+        Err(e) => {
+            eprintln!("pyreq: {}\n\n{}", e, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+    match run(command) {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(e) => {
+            eprintln!("pyreq: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(s: &str) -> Vec<String> {
+        s.split_whitespace().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_args() {
+        assert_eq!(
+            parse_args(&args("parse req.txt")),
+            Ok(Command::Parse(Some("req.txt".to_string())))
+        );
+        assert_eq!(
+            parse_args(&args("fmt --check a.txt b.txt")),
+            Ok(Command::Fmt {
+                check: true,
+                files: args("a.txt b.txt")
+            })
+        );
+        let Ok(Command::Eval { env, extras, file }) = parse_args(&args(
+            "eval --env python_version=3.8 --env=sys_platform=linux --extra socks -",
+        )) else {
+            panic!()
+        };
+        assert_eq!(
+            (env.python_version.as_str(), env.sys_platform.as_str()),
+            ("3.8", "linux")
+        );
+        assert_eq!(extras, args("socks"));
+        assert_eq!(file, Some("-".to_string()));
+        assert!(parse_args(&args("check")).is_err());
+        assert!(parse_args(&args("eval --env python=3")).is_err());
+        assert!(parse_args(&args("eval --extra")).is_err());
+        assert!(parse_args(&args("lint")).is_err());
+    }
+
+    #[test]
+    fn test_eval() {
+        let file: RequirementsFile = "--pre
+a; python_version < '3.8'
+b[x]>=1; python_version >= '3.8' and sys_platform == 'linux' # comment
+c; extra == 'socks'
+https://example.com/d-1.0.tar.gz
+./e[x]; sys_platform == 'win32'
+-e git+https://example.com/f.git#egg=f
+"
+        .parse()
+        .unwrap();
+        let mut env = MarkerEnvironment {
+            python_version: "3.10".to_string(),
+            sys_platform: "linux".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(
+            eval(&file, &env, &[]),
+            vec![
+                "b[x]>=1; python_version >= \"3.8\" and sys_platform == \"linux\"",
+                "https://example.com/d-1.0.tar.gz",
+                "-e git+https://example.com/f.git#egg=f"
+            ]
+        );
+        env.python_version = "3.7".to_string();
+        assert_eq!(
+            eval(&file, &env, &args("socks")),
+            vec![
+                "a; python_version < \"3.8\"",
+                "c; extra == \"socks\"",
+                "https://example.com/d-1.0.tar.gz",
+                "-e git+https://example.com/f.git#egg=f"
+            ]
+        );
+        let json = file_json(&file);
+        assert_eq!(json["lines"][0]["content"]["options"][0]["name"], "--pre");
+        assert_eq!(
+            json["lines"][2]["content"]["requirement"]["specifier"]["marker"]["and"][1],
+            json!({"basic": {
+                "lhs": {"variable": "sys_platform"},
+                "op": "==",
+                "rhs": {"literal": "linux"}
+            }})
+        );
+        assert_eq!(json["lines"][2]["comment"], " comment");
+        assert_eq!(
+            json["lines"][6]["content"]["requirement"]["location"],
+            json!({"vcs": {
+                "backend": "git",
+                "url": "https://example.com/f.git",
+                "rev": null,
+                "egg": "f",
+                "subdirectory": null,
+                "params": []
+            }})
+        );
+        assert_eq!(
+            json["lines"][5]["content"]["requirement"],
+            json!({
+                "type": "project",
+                "location": {"path": "./e"},
+                "extras": ["x"],
+                "editable": false,
+                "marker": {"basic": {
+                    "lhs": {"variable": "sys_platform"},
+                    "op": "==",
+                    "rhs": {"literal": "win32"}
+                }}
+            })
+        );
+    }
+}
@@ -0,0 +1,263 @@
+//! 解析错误, 记录出错的位置(span, 行号, 列号)和期望的token
+//! nom的默认错误只有剩余的输入和ErrorKind, alt也只会返回最后一个分支的错误, 不能直接用来提示用户.
+//! 所以parser使用自定义的错误类型Expected: 出错处剩余的输入和期望的token, 由expect标注;
+//! alt的分支都失败时取走得最远的错误, 确定了分支之后(如marker中的';'和'and')用cut, 避免opt和many0丢掉更深处的错误.
+use std::{cmp::Ordering, fmt::Display, ops::Range};
+
+use nom::{
+    character::complete::multispace0,
+    combinator::eof,
+    error::{ErrorKind, FromExternalError},
+    sequence::{preceded, terminated},
+    Finish, InputLength, Parser,
+};
+
+// parser中统一使用的IResult
+pub type IResult<I, O> = nom::IResult<I, O, Expected<I>>;
+
+// nom的错误类型: 出错处剩余的输入和期望的token; expected为空表示还没有标注, 由外层的expect标注
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Expected<I> {
+    pub input: I,
+    pub expected: &'static str,
+}
+
+impl<I> Expected<I> {
+    pub fn new(input: I, expected: &'static str) -> Self {
+        Self { input, expected }
+    }
+}
+
+impl<I: InputLength> nom::error::ParseError<I> for Expected<I> {
+    fn from_error_kind(input: I, _: ErrorKind) -> Self {
+        Self::new(input, "")
+    }
+
+    fn append(_: I, _: ErrorKind, other: Self) -> Self {
+        other
+    }
+
+    // 取走得最远的错误, 一样远时取后一个分支标注过的
+    fn or(self, other: Self) -> Self {
+        match other.input.input_len().cmp(&self.input.input_len()) {
+            Ordering::Less => other,
+            Ordering::Greater => self,
+            Ordering::Equal if other.expected.is_empty() => self,
+            Ordering::Equal => other,
+        }
+    }
+}
+
+impl<I, E> FromExternalError<I, E> for Expected<I> {
+    fn from_external_error(input: I, _: ErrorKind, _: E) -> Self {
+        Self::new(input, "")
+    }
+}
+
+// 标注parser期望的token: 错误没有标注, 或者没有越过token开头时, 报告为在token开头(不包括前边的空白)期望expected
+pub fn expect<'a, O>(
+    expected: &'static str,
+    mut parser: impl Parser<&'a str, O, Expected<&'a str>>,
+) -> impl FnMut(&'a str) -> IResult<&'a str, O> {
+    move |input: &'a str| {
+        parser.parse(input).map_err(|e| {
+            e.map(|e| {
+                let start = input.trim_start();
+                if e.expected.is_empty() || e.input.len() >= start.len() {
+                    Expected::new(start, expected)
+                } else {
+                    e
+                }
+            })
+        })
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ParseError {
+    // 出错的字符在源文本中的字节范围, 到达输入末尾时为空
+    pub span: Range<usize>,
+    // 从1开始
+    pub line: usize,
+    // 从1开始, 按字符计数
+    pub column: usize,
+    pub expected: String,
+    pub message: String,
+}
+
+impl ParseError {
+    pub fn new(source: &str, offset: usize, expected: impl Into<String>) -> Self {
+        let expected = expected.into();
+        let before = &source[..offset];
+        let line = before.matches('\n').count() + 1;
+        let column = before[before.rfind('\n').map_or(0, |i| i + 1)..]
+            .chars()
+            .count()
+            + 1;
+        let found = source[offset..].chars().next();
+        let message = match found {
+            Some(c) => format!("expected {}, found {:?}", expected, c),
+            None => format!("expected {}, found end of input", expected),
+        };
+        Self {
+            span: offset..offset + found.map_or(0, |c| c.len_utf8()),
+            line,
+            column,
+            expected,
+            message,
+        }
+    }
+
+    // remaining是source的后缀, 即nom返回的剩余输入
+    pub fn at(source: &str, remaining: &str, expected: impl Into<String>) -> Self {
+        Self::new(source, source.len() - remaining.len(), expected)
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+// 要求parser消费全部输入(允许末尾的空白), 否则在剩余的输入处期望end
+pub fn parse_complete<'a, T>(
+    source: &'a str,
+    parser: impl FnMut(&'a str) -> IResult<&'a str, T>,
+    end: &'static str,
+) -> Result<T, ParseError> {
+    terminated(parser, expect(end, preceded(multispace0, eof)))(source)
+        .finish()
+        .map(|(_, v)| v)
+        .map_err(|e| {
+            let expected = match e.expected {
+                "" => "valid input",
+                expected => expected,
+            };
+            ParseError::at(source, e.input, expected)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::ParseError;
+    use crate::requirements::{MarkerExpr, RequirementSpecifier, RequirementsFile, Version};
+
+    fn spec_error(s: &str) -> (usize, usize, String) {
+        let e = RequirementSpecifier::from_str(s).unwrap_err();
+        (e.span.start, e.column, e.expected)
+    }
+
+    #[test]
+    fn test_specification_error() {
+        assert_eq!(spec_error(""), (0, 1, "package name".to_string()));
+        assert_eq!(spec_error("  -name"), (2, 3, "package name".to_string()));
+        assert_eq!(spec_error("name>="), (6, 7, "version".to_string()));
+        assert_eq!(
+            spec_error("name >= 1.0 ,"),
+            (13, 14, "version operator".to_string())
+        );
+        assert_eq!(
+            spec_error("cryptography (>=3.3.2<4)"),
+            (21, 22, "','".to_string())
+        );
+        assert_eq!(
+            spec_error("name (>=1.0"),
+            (11, 12, "',' or ')'".to_string())
+        );
+        assert_eq!(spec_error("name[a b]"), (7, 8, "',' or ']'".to_string()));
+        assert_eq!(spec_error("name[a,]"), (7, 8, "extra name".to_string()));
+        assert_eq!(
+            spec_error("name; python_version = '3'"),
+            (21, 22, "marker operator".to_string())
+        );
+        assert_eq!(
+            spec_error("name; (os_name == 'a' or os_name == 'b'"),
+            (39, 40, "'and', 'or' or ')'".to_string())
+        );
+        assert_eq!(
+            spec_error("name; os_name == 'a' xor"),
+            (21, 22, "'and' or 'or'".to_string())
+        );
+        assert_eq!(
+            spec_error("name foo"),
+            (5, 6, "version specifier, '[', '@' or ';'".to_string())
+        );
+        assert_eq!(
+            spec_error("name @ http://foo.com;python_version<'3'"),
+            (36, 37, "whitespace after URL".to_string())
+        );
+        assert_eq!(
+            RequirementSpecifier::from_str("name>=1.0 foo")
+                .unwrap_err()
+                .to_string(),
+            "1:11: expected ',' or ';', found 'f'"
+        );
+        assert!(RequirementSpecifier::from_str("  name >= 1.0 ; os_name == 'nt'  ").is_ok());
+    }
+
+    #[test]
+    fn test_version_and_marker_error() {
+        assert_eq!(
+            Version::from_str("1.0foo").unwrap_err(),
+            ParseError {
+                span: 3..4,
+                line: 1,
+                column: 4,
+                expected: "end of version".to_string(),
+                message: "expected end of version, found 'f'".to_string(),
+            }
+        );
+        assert_eq!(
+            Version::from_str("x").unwrap_err().expected,
+            "release number"
+        );
+        assert!(Version::from_str(" 1.0.post1 ").is_ok());
+        assert_eq!(
+            MarkerExpr::from_str("os_name == 'a' and").unwrap_err().span,
+            18..18
+        );
+    }
+
+    #[test]
+    fn test_requirements_file_error() {
+        let e =
+            RequirementsFile::from_str("foo\n# comment\nbar >= \\\n  1.0 \\\n  <2\n").unwrap_err();
+        assert_eq!((e.line, e.column), (5, 3));
+        assert_eq!(e.expected, "','");
+        let e = RequirementsFile::from_str("--index-url\n").unwrap_err();
+        assert_eq!(
+            (e.line, e.column, e.expected.as_str()),
+            (1, 12, "option value")
+        );
+        let e = RequirementsFile::from_str("foo --hsah=abc\n").unwrap_err();
+        assert_eq!(
+            (e.line, e.column, e.expected.as_str()),
+            (1, 5, "pip option")
+        );
+        let e = RequirementsFile::from_str("foo --hash=sha256:zz\n").unwrap_err();
+        assert_eq!(
+            (e.line, e.column, e.expected.as_str()),
+            (1, 12, "sha256, sha384 or sha512 hash")
+        );
+        let e = RequirementsFile::from_str("--pre=1").unwrap_err();
+        assert_eq!(
+            (e.line, e.column, e.expected.as_str()),
+            (1, 6, "whitespace")
+        );
+        let e = RequirementsFile::from_str("-e https://example.com/foo.zip\n").unwrap_err();
+        assert_eq!(
+            (e.line, e.column, e.expected.as_str()),
+            (1, 4, "local project path or VCS URL")
+        );
+        let e = RequirementsFile::from_str("./foo[bar] ; os_name = 'nt'").unwrap_err();
+        assert_eq!(
+            (e.line, e.column, e.expected.as_str()),
+            (1, 22, "marker operator")
+        );
+    }
+}
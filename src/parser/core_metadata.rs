@@ -0,0 +1,234 @@
+//! 解析wheel中的METADATA和sdist中的PKG-INFO, 见<https://packaging.python.org/en/latest/specifications/core-metadata/>
+//! 格式是RFC 822风格的header: "Name: value", 以空白开头的行是上一个字段的续行, 第一个空行之后是正文(Description)
+//! 字段名不区分大小写; 依赖相关的字段用specification和versionspec解析, 出错的位置对应到整个文件
+//! Requires-Dist用宽松模式解析; 仍然无法解析的Requires-Dist, 以及不合法的Requires-Python和Provides-Extra单独记录, 不影响其他字段
+//! Metadata-Version, Name和Version重复时报错; 重复的Requires-Python放在other中
+use std::str::FromStr;
+
+use nom::{
+    bytes::complete::take_while1,
+    character::complete::{char as nomchar, space0},
+    combinator::rest,
+    sequence::{preceded, separated_pair},
+    IResult, Offset,
+};
+
+use super::lenient::{lenient_specification, LenientWarning};
+use crate::{
+    error::ParseError,
+    requirements::{
+        metadata::{CoreMetadata, METADATA_VERSIONS},
+        requires_python::RequiresPython,
+        ExtraName, PackageName, RequirementSpecifier, Version,
+    },
+};
+
+// 除了':'以外的可见ASCII字符, 见RFC 5322的ftext
+pub fn field_name(input: &str) -> IResult<&str, &str> {
+    take_while1(|c: char| c.is_ascii_graphic() && c != ':')(input)
+}
+
+pub fn field_line(input: &str) -> IResult<&str, (&str, &str)> {
+    separated_pair(field_name, nomchar(':'), preceded(space0, rest))(input)
+}
+
+// 合并续行之前的一个字段
+struct RawField<'a> {
+    name: &'a str,
+    // 第一行的值在源文本中的偏移
+    offset: usize,
+    lines: Vec<&'a str>,
+}
+
+impl RawField<'_> {
+    // Description的续行以7个空格和'|'或者8个空格开头, 保留换行; 其他字段按RFC 822合并为一行
+    fn value(&self) -> String {
+        if self.name.eq_ignore_ascii_case("description") {
+            let mut value = self.lines[0].to_string();
+            for line in &self.lines[1..] {
+                let line = match (line.strip_prefix("       |"), line.strip_prefix("        ")) {
+                    (Some(l), _) | (None, Some(l)) => l,
+                    (None, None) => line.trim_start(),
+                };
+                value.push('\n');
+                value.push_str(line);
+            }
+            value
+        } else {
+            let lines: Vec<&str> = self.lines.iter().map(|l| l.trim()).collect();
+            lines.join(" ").trim_end().to_string()
+        }
+    }
+
+    // 值中的位置对应到源文本; 只有第一行的位置是准确的, 其他的指向值的开头
+    fn source_offset(&self, offset: usize) -> usize {
+        if offset <= self.lines[0].trim_end().len() {
+            self.offset + offset
+        } else {
+            self.offset
+        }
+    }
+
+    fn parse<T: FromStr<Err = ParseError>>(&self, input: &str) -> Result<T, ParseError> {
+        self.value().parse().map_err(|e: ParseError| {
+            ParseError::new(input, self.source_offset(e.span.start), e.expected)
+        })
+    }
+
+    // 旧的元数据中常有不符合PEP 508的Requires-Dist, 如 bar (>=1.0<2), 用宽松模式解析
+    fn parse_lenient(
+        &self,
+        input: &str,
+    ) -> Result<(RequirementSpecifier, Vec<LenientWarning>), ParseError> {
+        match lenient_specification(&self.value()) {
+            Ok((req, warnings)) => Ok((
+                req,
+                warnings
+                    .into_iter()
+                    .map(|w| LenientWarning {
+                        offset: self.source_offset(w.offset),
+                        ..w
+                    })
+                    .collect(),
+            )),
+            Err(e) => Err(ParseError::new(
+                input,
+                self.source_offset(e.span.start),
+                e.expected,
+            )),
+        }
+    }
+}
+
+// 返回(字段, 正文)
+fn split_fields(input: &str) -> Result<(Vec<RawField<'_>>, Option<&str>), ParseError> {
+    let mut fields: Vec<RawField> = vec![];
+    let mut offset = 0;
+    for raw in input.split_inclusive('\n') {
+        let line = raw.trim_end_matches(['\n', '\r']);
+        let line_offset = offset;
+        offset += raw.len();
+        if line.is_empty() {
+            return Ok((fields, Some(&input[offset..]).filter(|b| !b.is_empty())));
+        }
+        if line.starts_with([' ', '\t']) {
+            match fields.last_mut() {
+                Some(field) => field.lines.push(line),
+                None => return Err(ParseError::new(input, line_offset, "field name")),
+            }
+            continue;
+        }
+        match field_line(line) {
+            Ok((_, (name, value))) => fields.push(RawField {
+                name,
+                offset: line_offset + (line.len() - value.len()),
+                lines: vec![value],
+            }),
+            Err(_) => {
+                let at = field_name(line).map_or(0, |(r, _)| line.len() - r.len());
+                let expected = if at == 0 { "field name" } else { "':'" };
+                return Err(ParseError::new(input, line_offset + at, expected));
+            }
+        }
+    }
+    Ok((fields, None))
+}
+
+pub fn core_metadata(input: &str) -> Result<CoreMetadata, ParseError> {
+    let (fields, body) = split_fields(input)?;
+    let headers_end = input.len() - body.map_or(0, |b| b.len());
+    let mut metadata = CoreMetadata::default();
+    let (mut metadata_version, mut name, mut version) = (None, None, None);
+    for field in &fields {
+        // 只能出现一次的字段取第一个值
+        let set = |slot: &mut Option<String>| {
+            slot.get_or_insert_with(|| field.value());
+        };
+        // Metadata-Version, Name和Version决定了是哪个包, 重复时无法确定以哪个为准
+        let duplicate = || {
+            ParseError::new(
+                input,
+                input.offset(field.name),
+                format!("a single {} field", field.name),
+            )
+        };
+        match field.name.to_ascii_lowercase().as_str() {
+            "metadata-version" if metadata_version.is_some() => return Err(duplicate()),
+            "name" if name.is_some() => return Err(duplicate()),
+            "version" if version.is_some() => return Err(duplicate()),
+            "metadata-version" => {
+                let value = field.value();
+                if !METADATA_VERSIONS.contains(&value.as_str()) {
+                    return Err(ParseError::new(
+                        input,
+                        field.offset,
+                        "metadata version 1.0 to 2.4",
+                    ));
+                }
+                metadata_version = Some(value);
+            }
+            "name" => name = Some(field.parse::<PackageName>(input)?),
+            "version" => version = Some(field.parse::<Version>(input)?),
+            "summary" => set(&mut metadata.summary),
+            "description" => set(&mut metadata.description),
+            "description-content-type" => set(&mut metadata.description_content_type),
+            "keywords" => set(&mut metadata.keywords),
+            "home-page" => set(&mut metadata.home_page),
+            "download-url" => set(&mut metadata.download_url),
+            "author" => set(&mut metadata.author),
+            "author-email" => set(&mut metadata.author_email),
+            "maintainer" => set(&mut metadata.maintainer),
+            "maintainer-email" => set(&mut metadata.maintainer_email),
+            "license" => set(&mut metadata.license),
+            "license-expression" => set(&mut metadata.license_expression),
+            "license-file" => metadata.license_files.push(field.value()),
+            "classifier" => metadata.classifiers.push(field.value()),
+            "platform" => metadata.platforms.push(field.value()),
+            "supported-platform" => metadata.supported_platforms.push(field.value()),
+            "project-url" => {
+                let value = field.value();
+                let (label, url) = value.split_once(',').unwrap_or(("", &value));
+                metadata
+                    .project_urls
+                    .push((label.trim().to_string(), url.trim().to_string()));
+            }
+            // 一个依赖无法解析时不影响其他字段
+            "requires-dist" => match field.parse_lenient(input) {
+                Ok((req, warnings)) => {
+                    metadata.requires_dist.push(req);
+                    metadata.requires_dist_warnings.extend(warnings);
+                }
+                Err(e) => metadata.invalid_requires_dist.push((field.value(), e)),
+            },
+            // 旧的元数据中常有不符合PEP 440的Requires-Python, 如>=3.6.*, 和Requires-Dist一样单独记录
+            "requires-python"
+                if metadata.requires_python.is_none()
+                    && metadata.invalid_requires_python.is_none() =>
+            {
+                match field.parse::<RequiresPython>(input) {
+                    Ok(requires_python) => metadata.requires_python = Some(requires_python),
+                    Err(e) => metadata.invalid_requires_python = Some((field.value(), e)),
+                }
+            }
+            "requires-external" => metadata.requires_external.push(field.value()),
+            "provides-extra" => match field.parse::<ExtraName>(input) {
+                Ok(extra) => metadata.provides_extra.push(extra),
+                Err(e) => metadata.invalid_provides_extra.push((field.value(), e)),
+            },
+            "provides-dist" => metadata.provides_dist.push(field.value()),
+            "obsoletes-dist" => metadata.obsoletes_dist.push(field.value()),
+            "dynamic" => metadata.dynamic.push(field.value()),
+            _ => metadata.other.push((field.name.to_string(), field.value())),
+        }
+    }
+    let missing = |field| ParseError::new(input, headers_end, field);
+    metadata.metadata_version =
+        metadata_version.ok_or_else(|| missing("Metadata-Version field"))?;
+    metadata.name = name.ok_or_else(|| missing("Name field"))?;
+    metadata.version = version.ok_or_else(|| missing("Version field"))?;
+    // 2.1开始正文也可以是Description
+    if let Some(body) = body {
+        metadata.description.get_or_insert_with(|| body.to_string());
+    }
+    Ok(metadata)
+}
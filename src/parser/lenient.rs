@@ -0,0 +1,322 @@
+//! 宽松模式, 修复PyPI元数据中常见的不规范写法(见fixtures/unrecognized.txt), 并记录每一处修复
+//! 只有严格模式解析失败时才会尝试修复, 修复后的VersionSpec仍然要通过严格模式的version_one
+use std::fmt::Display;
+
+use nom::{
+    branch::alt,
+    bytes::complete::{tag, take_till1, take_while},
+    character::complete::{char as nomchar, one_of, space0},
+    combinator::{opt, recognize},
+    sequence::{preceded, tuple},
+};
+
+use super::{
+    requirement_specifier::{extras, identifier, version_arbitraryequal, version_cmp, version_one},
+    version::{dev, epoch, local, post, pre, release, version_scheme},
+};
+use crate::{
+    error::{Expected, IResult, ParseError},
+    requirements::{
+        Comparison, MarkerExpr, PackageName, RequirementSpecifier, VersionPattern, VersionSpec,
+    },
+};
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Recovery {
+    // >=3.3.2<4 => >=3.3.2,<4
+    MissingComma,
+    // >==3.2.1 => >=3.2.1
+    RepeatedEquals,
+    // >="0.19.0" => >=0.19.0
+    QuotedVersion,
+    // 只有==和!=允许wildcard, 如 >=1.21.* => >=1.21, <=5.9.* => <5.10
+    OrderingWildcard {
+        original: String,
+        normalized: String,
+    },
+    // 只有==, !=和===允许local version, 如 >=3.4.2+contrib => >=3.4.2
+    LocalVersionDropped(String),
+    // 括号中无法识别的内容, 通常是漏了换行的下一个依赖, 如 (>=0.24seaborn)
+    TrailingTextDropped(String),
+    // PEP 345中的变量名, 如 sys.platform => sys_platform
+    LegacyMarkerVariable {
+        original: String,
+        normalized: &'static str,
+    },
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct LenientWarning {
+    // 在源文本中的字节偏移
+    pub offset: usize,
+    pub recovery: Recovery,
+}
+
+impl Display for LenientWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: ", self.offset)?;
+        match &self.recovery {
+            Recovery::MissingComma => write!(f, "inserted missing ','"),
+            Recovery::RepeatedEquals => write!(f, "removed repeated '='"),
+            Recovery::QuotedVersion => write!(f, "removed quotes around version"),
+            Recovery::OrderingWildcard {
+                original,
+                normalized,
+            } => write!(
+                f,
+                "replaced wildcard version {:?} with {:?}",
+                original, normalized
+            ),
+            Recovery::LocalVersionDropped(l) => write!(f, "dropped local version {:?}", l),
+            Recovery::TrailingTextDropped(t) => write!(f, "dropped unrecognized text {:?}", t),
+            Recovery::LegacyMarkerVariable {
+                original,
+                normalized,
+            } => write!(
+                f,
+                "replaced legacy marker variable {:?} with {:?}",
+                original, normalized
+            ),
+        }
+    }
+}
+
+// 修复的位置用剩余的输入表示, 最后再换算成偏移
+pub type Fixes<'a> = Vec<(&'a str, Recovery)>;
+
+fn fail<T>(input: &str) -> IResult<&str, T> {
+    Err(nom::Err::Error(Expected::new(input, "")))
+}
+
+// release最后一位加1, 用于把wildcard转换为上界
+fn next_prefix(prefix: &str) -> Option<String> {
+    let (_, mut v) = version_scheme(prefix).ok()?;
+    *v.release.last_mut()? += 1;
+    Some(v.to_string())
+}
+
+pub fn lenient_version_one(input: &str) -> IResult<&str, (VersionSpec, Fixes<'_>)> {
+    let (rest, mut op) = version_cmp(input)?;
+    let mut fixes = vec![];
+    let (after_repeated, repeated) = take_while(|c| c == '=')(rest)?;
+    if !repeated.is_empty() {
+        match op {
+            Comparison::LessThanOrEqual | Comparison::GreaterThanOrEqual | Comparison::NotEqual => {
+                fixes.push((rest, Recovery::RepeatedEquals))
+            }
+            _ => return fail(rest),
+        }
+    }
+    let rest = after_repeated;
+    if op == Comparison::ArbitraryEqual {
+        let (rest, v) = version_arbitraryequal(rest)?;
+        return Ok((rest, (VersionSpec(op, VersionPattern::Arbitrary(v)), fixes)));
+    }
+    let (rest, _) = space0(rest)?;
+    let (rest, open_quote) = opt(one_of("'\""))(rest)?;
+    let version_start = rest;
+    let (mut rest, mut raw) = recognize(tuple((
+        opt(nomchar('v')),
+        opt(epoch),
+        release,
+        opt(alt((
+            tag(".*"),
+            recognize(tuple((opt(pre), opt(post), opt(dev), opt(local)))),
+        ))),
+    )))(version_start)?;
+    // 版本号后边直接跟着字母或数字
+    // 如 (>=1.1.7dev-r23744), 去掉末尾的分隔符得到1.1.7dev
+    // 如 (>=3requests>=2), 不能把"r"当作post-release, 只保留release部分
+    if rest.starts_with(|c: char| c.is_ascii_alphanumeric()) {
+        let trimmed = raw.trim_end_matches(['-', '_', '.']);
+        if trimmed.len() < raw.len() {
+            (rest, raw) = (&version_start[trimmed.len()..], trimmed);
+        } else {
+            (rest, raw) =
+                recognize(tuple((opt(nomchar('v')), opt(epoch), release)))(version_start)?;
+        }
+    }
+    let (rest, close_quote) = opt(one_of("'\""))(rest)?;
+    if open_quote.is_some() || close_quote.is_some() {
+        fixes.push((version_start, Recovery::QuotedVersion));
+    }
+    let mut version = raw.to_string();
+    if let Some(prefix) = raw.strip_suffix(".*") {
+        if !matches!(op, Comparison::Equal | Comparison::NotEqual) {
+            // X.*包含所有以X开头的版本
+            let (new_op, normalized) = match op {
+                Comparison::LessThanOrEqual => (Comparison::LessThan, next_prefix(prefix)),
+                Comparison::GreaterThan => (Comparison::GreaterThanOrEqual, next_prefix(prefix)),
+                Comparison::CompatibleRelease => (Comparison::Equal, Some(raw.to_string())),
+                _ => (op, Some(prefix.to_string())),
+            };
+            let Some(normalized) = normalized else {
+                return fail(version_start);
+            };
+            fixes.push((
+                version_start,
+                Recovery::OrderingWildcard {
+                    original: format!("{}{}", op.as_str(), raw),
+                    normalized: format!("{}{}", new_op.as_str(), normalized),
+                },
+            ));
+            op = new_op;
+            version = normalized;
+        }
+    } else if let Some(i) = raw.find('+') {
+        if !matches!(op, Comparison::Equal | Comparison::NotEqual) {
+            fixes.push((
+                &version_start[i..],
+                Recovery::LocalVersionDropped(raw[i + 1..].to_string()),
+            ));
+            version = raw[..i].to_string();
+        }
+    }
+    // 修复后的结果仍然要符合严格模式的语法
+    match version_one(&format!("{}{}", op.as_str(), version)) {
+        Ok(("", spec)) => Ok((rest, (spec, fixes))),
+        _ => fail(version_start),
+    }
+}
+
+// in_parens: 在括号中时, 丢弃无法识别的内容直到下一个','或')'
+pub fn lenient_version_many(
+    input: &str,
+    in_parens: bool,
+) -> IResult<&str, (Vec<VersionSpec>, Fixes<'_>)> {
+    let (mut rest, (first, mut fixes)) = lenient_version_one(input)?;
+    let mut specs = vec![first];
+    loop {
+        let (r, _) = space0(rest)?;
+        let r = if let Some(r) = r.strip_prefix(',') {
+            r
+        } else if version_cmp(r).is_ok() {
+            fixes.push((r, Recovery::MissingComma));
+            r
+        } else if in_parens && !r.is_empty() && !r.starts_with(')') {
+            let (after, junk) = take_till1(|c| c == ',' || c == ')')(r)?;
+            fixes.push((r, Recovery::TrailingTextDropped(junk.to_string())));
+            rest = after;
+            continue;
+        } else {
+            return Ok((r, (specs, fixes)));
+        };
+        let (r, (spec, mut f)) = lenient_version_one(r)?;
+        specs.push(spec);
+        fixes.append(&mut f);
+        rest = r;
+    }
+}
+
+pub fn lenient_versionspec(input: &str) -> IResult<&str, (Vec<VersionSpec>, Fixes<'_>)> {
+    match input.strip_prefix('(') {
+        Some(r) => {
+            let (r, v) = lenient_version_many(r, true)?;
+            let (r, _) = preceded(space0, nomchar(')'))(r)?;
+            Ok((r, v))
+        }
+        None => lenient_version_many(input, false),
+    }
+}
+
+// see https://peps.python.org/pep-0345/#environment-markers
+static LEGACY_MARKER_VARIABLES: [(&str, &str); 6] = [
+    ("os.name", "os_name"),
+    ("sys.platform", "sys_platform"),
+    ("platform.version", "platform_version"),
+    ("platform.machine", "platform_machine"),
+    (
+        "platform.python_implementation",
+        "platform_python_implementation",
+    ),
+    ("python_implementation", "platform_python_implementation"),
+];
+
+// 替换引号以外的旧变量名, 返回替换后的文本和(在input中的偏移, 修复)
+pub fn normalize_legacy_marker(input: &str) -> (String, Vec<(usize, Recovery)>) {
+    let is_word = |c: char| c.is_ascii_alphanumeric() || c == '_' || c == '.';
+    let mut output = String::new();
+    let mut fixes = vec![];
+    let mut quote = None;
+    let mut i = 0;
+    while let Some(c) = input[i..].chars().next() {
+        if quote.is_none() && !input[..i].ends_with(is_word) {
+            let rest = &input[i..];
+            let legacy = LEGACY_MARKER_VARIABLES
+                .iter()
+                .find(|(old, _)| rest.starts_with(old) && !rest[old.len()..].starts_with(is_word));
+            if let Some((old, new)) = legacy {
+                output.push_str(new);
+                fixes.push((
+                    i,
+                    Recovery::LegacyMarkerVariable {
+                        original: old.to_string(),
+                        normalized: new,
+                    },
+                ));
+                i += old.len();
+                continue;
+            }
+        }
+        match quote {
+            Some(q) if q == c => quote = None,
+            None if c == '\'' || c == '"' => quote = Some(c),
+            _ => {}
+        }
+        output.push(c);
+        i += c.len_utf8();
+    }
+    (output, fixes)
+}
+
+// 先按严格模式解析, 失败时再尝试修复; 无法修复时返回严格模式的错误
+pub fn lenient_specification(
+    source: &str,
+) -> Result<(RequirementSpecifier, Vec<LenientWarning>), ParseError> {
+    let strict_error = match source.parse::<RequirementSpecifier>() {
+        Ok(r) => return Ok((r, vec![])),
+        Err(e) => e,
+    };
+    let offset = |rest: &str| source.len() - rest.len();
+    let Ok((rest, (name, e, v, _))) = tuple((
+        preceded(space0, identifier),
+        preceded(space0, opt(extras)),
+        preceded(space0, opt(lenient_versionspec)),
+        space0,
+    ))(source) else {
+        return Err(strict_error);
+    };
+    let (version_specs, fixes) = v.unwrap_or_default();
+    let mut warnings: Vec<LenientWarning> = fixes
+        .into_iter()
+        .map(|(at, recovery)| LenientWarning {
+            offset: offset(at),
+            recovery,
+        })
+        .collect();
+    let marker_expr = match rest.strip_prefix(';') {
+        Some(marker) => {
+            let (normalized, fixes) = normalize_legacy_marker(marker);
+            let Ok(m) = normalized.parse::<MarkerExpr>() else {
+                return Err(strict_error);
+            };
+            warnings.extend(fixes.into_iter().map(|(i, recovery)| LenientWarning {
+                offset: offset(marker) + i,
+                recovery,
+            }));
+            Some(m)
+        }
+        None if rest.is_empty() => None,
+        None => return Err(strict_error),
+    };
+    Ok((
+        RequirementSpecifier {
+            name: PackageName::new(name),
+            extras: e.flatten().unwrap_or_default(),
+            version_specs,
+            marker_expr,
+            ..Default::default()
+        },
+        warnings,
+    ))
+}
@@ -0,0 +1,206 @@
+//! 读取pyproject.toml中的依赖; TOML由toml_edit解析(保留每个值的位置), 依赖字符串再用specification解析
+//! 和core_metadata一样, 所有的错误(包括TOML语法错误和类型错误)都用ParseError表示, 位置对应到整个文件
+use std::{ops::Range, str::FromStr};
+
+use toml_edit::{ImDocument, Item, TableLike, Value};
+
+use crate::{
+    error::ParseError,
+    requirements::{
+        pyproject::{DependencyGroupItem, PyProject, Spanned},
+        requires_python::RequiresPython,
+        ExtraName, PackageName, RequirementSpecifier,
+    },
+};
+
+fn line_of(input: &str, offset: usize) -> usize {
+    input[..offset].matches('\n').count() + 1
+}
+
+fn start(span: Option<Range<usize>>) -> usize {
+    span.map_or(0, |s| s.start)
+}
+
+// 字符串字面量中的错误对应到源文本; 有转义或者多行字符串开头的换行时, 字面量和值对不上, 指向字面量的开头
+fn parse_literal<T: FromStr<Err = ParseError>>(
+    input: &str,
+    value: &str,
+    span: Range<usize>,
+) -> Result<Spanned<T>, ParseError> {
+    match value.parse() {
+        Ok(parsed) => Ok(Spanned {
+            value: parsed,
+            line: line_of(input, span.start),
+            span,
+        }),
+        Err(e) => {
+            let raw = &input[span.clone()];
+            let quote = if raw.starts_with("\"\"\"") || raw.starts_with("'''") {
+                3
+            } else {
+                1
+            };
+            let offset = match raw.get(quote..raw.len().saturating_sub(quote)) {
+                Some(inner) if inner == value => span.start + quote + e.span.start,
+                _ => span.start,
+            };
+            Err(ParseError::new(input, offset, e.expected))
+        }
+    }
+}
+
+fn string_value<'a>(input: &str, value: &'a Value) -> Result<(&'a str, Range<usize>), ParseError> {
+    let span = value.span().unwrap_or_default();
+    match value.as_str() {
+        Some(s) => Ok((s, span)),
+        None => Err(ParseError::new(input, span.start, "string")),
+    }
+}
+
+fn table<'a>(input: &str, item: &'a Item) -> Result<&'a dyn TableLike, ParseError> {
+    item.as_table_like()
+        .ok_or_else(|| ParseError::new(input, start(item.span()), "table"))
+}
+
+fn requirements(
+    input: &str,
+    item: &Item,
+) -> Result<Vec<Spanned<RequirementSpecifier>>, ParseError> {
+    let array = item
+        .as_array()
+        .ok_or_else(|| ParseError::new(input, start(item.span()), "array of strings"))?;
+    array
+        .iter()
+        .map(|value| {
+            let (s, span) = string_value(input, value)?;
+            parse_literal(input, s, span)
+        })
+        .collect()
+}
+
+// 依赖组中的一项: 依赖字符串, 或者{include-group = "name"}
+fn dependency_group_item(input: &str, value: &Value) -> Result<DependencyGroupItem, ParseError> {
+    if let Some(table) = value.as_inline_table() {
+        if let (1, Some(include)) = (table.len(), table.get("include-group")) {
+            let (s, span) = string_value(input, include)?;
+            return parse_literal(input, s, span).map(DependencyGroupItem::IncludeGroup);
+        }
+    }
+    let span = value.span().unwrap_or_default();
+    match value.as_str() {
+        Some(s) => parse_literal(input, s, span).map(DependencyGroupItem::Requirement),
+        None => Err(ParseError::new(
+            input,
+            span.start,
+            "string or {include-group = ...}",
+        )),
+    }
+}
+
+// 和PEP 735参考实现一样, 不存在的组和循环引用都是错误
+fn check_includes(
+    input: &str,
+    groups: &[(PackageName, Vec<DependencyGroupItem>)],
+    items: &[DependencyGroupItem],
+    stack: &mut Vec<PackageName>,
+) -> Result<(), ParseError> {
+    for item in items {
+        let DependencyGroupItem::IncludeGroup(include) = item else {
+            continue;
+        };
+        if stack.contains(&include.value) {
+            return Err(ParseError::new(
+                input,
+                include.span.start,
+                "acyclic include-group",
+            ));
+        }
+        let Some((_, included)) = groups.iter().find(|(n, _)| *n == include.value) else {
+            return Err(ParseError::new(
+                input,
+                include.span.start,
+                "existing dependency group",
+            ));
+        };
+        stack.push(include.value.clone());
+        check_includes(input, groups, included, stack)?;
+        stack.pop();
+    }
+    Ok(())
+}
+
+fn dependency_groups(
+    input: &str,
+    item: &Item,
+) -> Result<Vec<(PackageName, Vec<DependencyGroupItem>)>, ParseError> {
+    let table = table(input, item)?;
+    let mut groups: Vec<(PackageName, Vec<DependencyGroupItem>)> = vec![];
+    for (name, item) in table.iter() {
+        let key_span = table.get_key_value(name).and_then(|(k, _)| k.span());
+        let name = name
+            .parse::<PackageName>()
+            .map_err(|_| ParseError::new(input, start(key_span.clone()), "group name"))?;
+        if groups.iter().any(|(n, _)| *n == name) {
+            return Err(ParseError::new(
+                input,
+                start(key_span),
+                "unique dependency group name",
+            ));
+        }
+        let array = item
+            .as_array()
+            .ok_or_else(|| ParseError::new(input, start(item.span()), "array"))?;
+        let items = array
+            .iter()
+            .map(|value| dependency_group_item(input, value))
+            .collect::<Result<_, _>>()?;
+        groups.push((name, items));
+    }
+    for (name, items) in &groups {
+        check_includes(input, &groups, items, &mut vec![name.clone()])?;
+    }
+    Ok(groups)
+}
+
+pub fn pyproject(input: &str) -> Result<PyProject, ParseError> {
+    let document = ImDocument::parse(input).map_err(|e| {
+        let mut error = ParseError::new(input, start(e.span()), "valid TOML");
+        error.message = e.message().trim_end().to_string();
+        error
+    })?;
+    let mut pyproject = PyProject::default();
+    if let Some(build_system) = document.get("build-system") {
+        if let Some(requires) = table(input, build_system)?.get("requires") {
+            pyproject.build_requires = requirements(input, requires)?;
+        }
+    }
+    if let Some(project) = document.get("project") {
+        let project = table(input, project)?;
+        if let Some(item) = project.get("requires-python") {
+            let value = item
+                .as_value()
+                .ok_or_else(|| ParseError::new(input, start(item.span()), "string"))?;
+            let (s, span) = string_value(input, value)?;
+            pyproject.requires_python = Some(parse_literal::<RequiresPython>(input, s, span)?);
+        }
+        if let Some(dependencies) = project.get("dependencies") {
+            pyproject.dependencies = requirements(input, dependencies)?;
+        }
+        if let Some(optional) = project.get("optional-dependencies") {
+            let optional = table(input, optional)?;
+            for (extra, item) in optional.iter() {
+                let key_span = optional.get_key_value(extra).and_then(|(k, _)| k.span());
+                let extra = extra
+                    .parse::<ExtraName>()
+                    .map_err(|_| ParseError::new(input, start(key_span), "extra name"))?;
+                pyproject
+                    .optional_dependencies
+                    .push((extra, requirements(input, item)?));
+            }
+        }
+    }
+    if let Some(groups) = document.get("dependency-groups") {
+        pyproject.dependency_groups = dependency_groups(input, groups)?;
+    }
+    Ok(pyproject)
+}
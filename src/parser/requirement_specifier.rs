@@ -1,7 +1,13 @@
 //! 解析(requirement specifier)[https://pip.pypa.io/en/stable/reference/requirement-specifiers]
 //! refer to https://peps.python.org/pep-0508/ for the complete parsley grammar.
 //! -> pythonExpression 是表示解析'->'前面的一串语法, 对应的python返回值是什么
-use crate::requirements::{Comparison, MarkerExpr, MarkerOp, RequirementSpecifier, VersionSpec};
+use crate::{
+    error::{expect, IResult},
+    requirements::{
+        url::ParsedUrl, Comparison, ExtraName, MarkerExpr, MarkerOp, MarkerValue, PackageName,
+        RequirementSpecifier, VersionPattern, VersionSpec,
+    },
+};
 use nom::{
     branch::alt,
     bytes::complete::{tag, take_while, take_while_m_n},
@@ -9,13 +15,13 @@ use nom::{
         complete::{char as nomchar, digit0, digit1, hex_digit1, satisfy, space0, space1},
         is_alphabetic, is_alphanumeric, is_digit, is_hex_digit, is_space,
     },
-    combinator::{eof, map, opt, recognize},
+    combinator::{cut, eof, map, map_opt, not, opt, peek, recognize, success},
     multi::{count, many0, many1, many_m_n},
-    sequence::{delimited, preceded, separated_pair, terminated, tuple},
-    IResult, Parser,
+    sequence::{delimited, preceded, terminated, tuple},
+    Parser,
 };
 
-use super::version::{dev, epoch, local, post, pre, release};
+use super::version::{dev, epoch, local, post, pre, release, version_scheme};
 
 // wsp* = space0
 
@@ -27,11 +33,12 @@ pub fn version_cmp(input: &str) -> IResult<&str, Comparison> {
                 tag("<="),
                 tag("<"),
                 tag("!="),
+                // "==="要放在"=="前边
+                tag("==="),
                 tag("=="),
                 tag(">="),
                 tag(">"),
                 tag("~="),
-                tag("==="),
             )),
         ),
         |s| Comparison::try_from(s).unwrap(),
@@ -104,42 +111,65 @@ pub fn version_other_operator(input: &str) -> IResult<&str, String> {
     )(input)
 }
 
+// 识别出的版本在这里解析为Version, ".*"结尾时为前缀匹配
+fn version_pattern(s: String) -> Option<VersionPattern> {
+    let (prefix, wildcard) = match s.strip_suffix(".*") {
+        Some(prefix) => (prefix, true),
+        None => (s.as_str(), false),
+    };
+    match version_scheme(prefix) {
+        Ok(("", version)) => Some(VersionPattern::Version { version, wildcard }),
+        _ => None,
+    }
+}
+
 pub fn version_one(input: &str) -> IResult<&str, VersionSpec> {
-    let (input, operator) = version_cmp(input)?;
-    match operator {
-        Comparison::ArbitraryEqual => terminated(version_arbitraryequal, space0)
-            .map(|v| (operator, v).into())
-            .parse(input),
-        Comparison::Equal | Comparison::NotEqual => terminated(version_equal_notequal, space0)
-            .map(|v| (operator, v).into())
-            .parse(input),
-        Comparison::CompatibleRelease => terminated(version_compatiblerelease, space0)
-            .map(|v| (operator, v).into())
-            .parse(input),
-        _ => terminated(version_other_operator, space0)
-            .map(|v| (operator, v).into())
-            .parse(input),
+    let (input, operator) = expect("version operator", version_cmp)(input)?;
+    if operator == Comparison::ArbitraryEqual {
+        return terminated(version_arbitraryequal, space0)
+            .map(|v| VersionSpec(operator, VersionPattern::Arbitrary(v)))
+            .parse(input);
     }
+    let version: fn(&str) -> IResult<&str, String> = match operator {
+        Comparison::Equal | Comparison::NotEqual => version_equal_notequal,
+        Comparison::CompatibleRelease => version_compatiblerelease,
+        _ => version_other_operator,
+    };
+    terminated(expect("version", map_opt(version, version_pattern)), space0)
+        .map(|pattern| VersionSpec(operator, pattern))
+        .parse(input)
 }
 
+// ','之后必须是下一个版本约束
 pub fn version_many(input: &str) -> IResult<&str, Vec<VersionSpec>> {
-    version_one
-        .and(many0(preceded(space0.and(nomchar(',')), version_one)))
-        .map(|(one, mut v)| {
-            v.insert(0, one);
-            v
-        })
-        .parse(input)
+    terminated(
+        version_one.and(many0(preceded(space0.and(nomchar(',')), cut(version_one)))),
+        // 常见的错误: 漏写了逗号, 如 >=3.3.2<4
+        expect("','", not(version_cmp)),
+    )
+    .map(|(one, mut v)| {
+        v.insert(0, one);
+        v
+    })
+    .parse(input)
 }
 
 pub fn versionspec(input: &str) -> IResult<&str, Vec<VersionSpec>> {
-    delimited(nomchar('('), version_many, nomchar(')'))
-        .or(version_many)
-        .parse(input)
+    delimited(
+        nomchar('('),
+        version_many,
+        expect("',' or ')'", nomchar(')')),
+    )
+    .or(version_many)
+    .parse(input)
 }
 
-pub fn urlspec(input: &str) -> IResult<&str, &str> {
-    preceded(nomchar('@').and(space0), uri_reference)(input)
+// '@'之后必须是url
+pub fn urlspec(input: &str) -> IResult<&str, ParsedUrl> {
+    preceded(
+        nomchar('@'),
+        cut(preceded(space0, expect("URL", parsed_url))),
+    )(input)
 }
 
 pub fn marker_op(input: &str) -> IResult<&str, MarkerOp> {
@@ -187,54 +217,75 @@ pub fn env_var(input: &str) -> IResult<&str, &str> {
     ))(input)
 }
 
-pub fn marker_var(input: &str) -> IResult<&str, &str> {
-    preceded(space0, env_var.or(python_str))(input)
+pub fn marker_var(input: &str) -> IResult<&str, MarkerValue> {
+    preceded(
+        space0,
+        alt((
+            env_var.map(|v| MarkerValue::Variable(v.to_string())),
+            python_str.map(|s| MarkerValue::Literal(s.to_string())),
+        )),
+    )(input)
 }
 
 // 表达式优先级: op > () > and > or
 // marker_expr = basic | '(' marker_or ')'
-// marker_and = marker_expr 'and' marker_expr | marker_expr
-// marker_or = marker_and 'or' marker_and | marker_and
+// marker_and = marker_expr ('and' marker_expr)*
+// marker_or = marker_and ('or' marker_and)*
 // marker = marker_or
 // 从下往上看更容易理解
 
 pub fn marker_expr(input: &str) -> IResult<&str, MarkerExpr> {
     alt((
         // 不用考虑空格的问题，因为marker_var和marker_op都是只吃前边的空格，后边的空格不管
-        tuple((marker_var, marker_op, marker_var))
-            .map(|(left, op, right)| MarkerExpr::Basic(left.to_string(), op, right.to_string())),
+        tuple((
+            expect("marker variable or quoted string", marker_var),
+            expect("marker operator", marker_op),
+            expect("marker variable or quoted string", marker_var),
+        ))
+        .map(|(left, op, right)| MarkerExpr::Basic(left, op, right)),
         delimited(
             preceded(space0, nomchar('(')),
             marker_or,
-            preceded(space0, nomchar(')')),
+            expect("'and', 'or' or ')'", preceded(space0, nomchar(')'))),
         ),
     ))(input)
 }
 
+// 任意多个操作数, 左结合: a and b and c => And(And(a, b), c)
+// 'and'和'or'之后必须是操作数
 pub fn marker_and(input: &str) -> IResult<&str, MarkerExpr> {
-    alt((
-        separated_pair(
-            marker_expr,
+    marker_expr
+        .and(many0(preceded(
             delimited(space0, tag("and"), space0),
-            marker_expr,
-        )
-        .map(|(left, right)| MarkerExpr::And(Box::new(left), Box::new(right))),
-        marker_expr,
-    ))(input)
+            cut(marker_expr),
+        )))
+        .map(|(first, rest)| {
+            rest.into_iter().fold(first, |left, right| {
+                MarkerExpr::And(Box::new(left), Box::new(right))
+            })
+        })
+        .parse(input)
 }
 
 pub fn marker_or(input: &str) -> IResult<&str, MarkerExpr> {
-    alt((
-        separated_pair(marker_and, delimited(space0, tag("or"), space0), marker_and)
-            .map(|(left, right)| MarkerExpr::Or(Box::new(left), Box::new(right))),
-        marker_and,
-    ))(input)
+    marker_and
+        .and(many0(preceded(
+            delimited(space0, tag("or"), space0),
+            cut(marker_and),
+        )))
+        .map(|(first, rest)| {
+            rest.into_iter().fold(first, |left, right| {
+                MarkerExpr::Or(Box::new(left), Box::new(right))
+            })
+        })
+        .parse(input)
 }
 
 // marker = marker_or
 
+// ';'之后必须是marker
 pub fn quoted_marker(input: &str) -> IResult<&str, MarkerExpr> {
-    preceded(nomchar(';').and(space0), marker_or)(input)
+    preceded(nomchar(';').and(space0), cut(marker_or))(input)
 }
 
 pub fn identifier_end(input: &str) -> IResult<&str, &str> {
@@ -248,11 +299,13 @@ pub fn identifier(input: &str) -> IResult<&str, String> {
         .parse(input)
 }
 
-pub fn extras_list(input: &str) -> IResult<&str, Vec<String>> {
+// ','之后必须是extra名
+pub fn extras_list(input: &str) -> IResult<&str, Vec<ExtraName>> {
     identifier
+        .map(ExtraName::new)
         .and(many0(preceded(
             delimited(space0, nomchar(','), space0),
-            identifier,
+            cut(expect("extra name", identifier.map(ExtraName::new))),
         )))
         .map(|(first, mut rest)| {
             rest.insert(0, first);
@@ -261,29 +314,55 @@ pub fn extras_list(input: &str) -> IResult<&str, Vec<String>> {
         .parse(input)
 }
 
-pub fn extras(input: &str) -> IResult<&str, Option<Vec<String>>> {
-    delimited(
-        nomchar('[').and(space0),
-        opt(extras_list),
-        space0.and(nomchar(']')),
+// '['之后必须是extras和']'
+pub fn extras(input: &str) -> IResult<&str, Option<Vec<ExtraName>>> {
+    preceded(
+        nomchar('['),
+        cut(delimited(
+            space0,
+            opt(extras_list),
+            expect("',' or ']'", space0.and(nomchar(']'))),
+        )),
     )
     .parse(input)
 }
 
+// 依赖的结尾: 输入的结尾, 或者requirements文件中同一行的选项
+fn requirement_end(input: &str) -> IResult<&str, ()> {
+    peek(preceded(space0, alt((eof, tag("-")))))
+        .map(|_| ())
+        .parse(input)
+}
+
+// [';' marker] 之后是依赖的结尾
+fn marker_tail(input: &str) -> IResult<&str, Option<MarkerExpr>> {
+    alt((
+        terminated(quoted_marker, expect("'and' or 'or'", requirement_end)).map(Some),
+        requirement_end.map(|_| None),
+    ))(input)
+}
+
 pub fn name_req(input: &str) -> IResult<&str, RequirementSpecifier> {
     tuple((
-        identifier,
+        expect("package name", identifier),
         space0,
         opt(extras),
         space0,
-        opt(versionspec),
-        space0,
-        opt(quoted_marker),
+        expect(
+            "version specifier, '[', '@' or ';'",
+            alt((
+                tuple((
+                    versionspec,
+                    preceded(space0, expect("',' or ';'", marker_tail)),
+                )),
+                tuple((success(vec![]), marker_tail)),
+            )),
+        ),
     ))
-    .map(|(i, _, e, _, v, _, m)| RequirementSpecifier {
-        name: i,
+    .map(|(i, _, e, _, (v, m))| RequirementSpecifier {
+        name: PackageName::new(i),
         extras: if let Some(Some(j)) = e { j } else { vec![] },
-        version_specs: if let Some(j) = v { j } else { vec![] },
+        version_specs: v,
         marker_expr: m,
         ..Default::default()
     })
@@ -297,13 +376,13 @@ pub fn url_req(input: &str) -> IResult<&str, RequirementSpecifier> {
         opt(extras),
         space0,
         urlspec,
-        alt((space1, eof)),
-        opt(quoted_marker),
+        expect("whitespace after URL", alt((space1, eof))),
+        expect("';'", marker_tail),
     ))
     .map(|(i, _, e, _, v, _, m)| RequirementSpecifier {
-        name: i,
+        name: PackageName::new(i),
         extras: if let Some(Some(j)) = e { j } else { vec![] },
-        urlspec: Some(v.to_string()),
+        urlspec: Some(v),
         marker_expr: m,
         ..Default::default()
     })
@@ -340,6 +419,85 @@ pub fn uri_reference(input: &str) -> IResult<&str, &str> {
     alt((uri, relative_ref))(input)
 }
 
+// 和authority相同, 返回(userinfo, host, port); port超出u16时失败
+fn authority_parts(input: &str) -> IResult<&str, (Option<&str>, &str, Option<u16>)> {
+    tuple((
+        opt(terminated(userinfo, nomchar('@'))),
+        host,
+        map_opt(
+            opt(preceded(nomchar(':'), digit0)),
+            |port: Option<&str>| match port {
+                None | Some("") => Some(None),
+                Some(port) => port.parse().ok().map(Some),
+            },
+        ),
+    ))(input)
+}
+
+type UrlParts<'a> = (Option<(Option<&'a str>, &'a str, Option<u16>)>, &'a str);
+
+fn hier_part_parts(input: &str) -> IResult<&str, UrlParts<'_>> {
+    alt((
+        preceded(tag("//"), tuple((authority_parts, path_abempty)))
+            .map(|(authority, path)| (Some(authority), path)),
+        alt((path_absolute, path_rootless, path_empty)).map(|path| (None, path)),
+    ))(input)
+}
+
+fn relative_part_parts(input: &str) -> IResult<&str, UrlParts<'_>> {
+    alt((
+        preceded(tag("//"), tuple((authority_parts, path_abempty)))
+            .map(|(authority, path)| (Some(authority), path)),
+        alt((path_absolute, path_noscheme, path_empty)).map(|path| (None, path)),
+    ))(input)
+}
+
+fn url_parts<'a>(
+    scheme: Option<&'a str>,
+    (authority, path): UrlParts<'a>,
+    query: Option<&'a str>,
+    fragment: Option<&'a str>,
+) -> ParsedUrl {
+    let (userinfo, host, port) = match authority {
+        Some((userinfo, host, port)) => (userinfo, Some(host), port),
+        None => (None, None, None),
+    };
+    ParsedUrl {
+        scheme: scheme.map(|s| s.to_string()),
+        userinfo: userinfo.map(|s| s.to_string()),
+        host: host.map(|s| s.to_string()),
+        port,
+        path: path.to_string(),
+        query: query.map(|s| s.to_string()),
+        fragment: fragment.map(|s| s.to_string()),
+    }
+}
+
+// 和uri相同, 保留各个部分
+pub fn parsed_uri(input: &str) -> IResult<&str, ParsedUrl> {
+    tuple((
+        terminated(scheme, nomchar(':')),
+        hier_part_parts,
+        opt(preceded(nomchar('?'), query)),
+        opt(preceded(nomchar('#'), fragment)),
+    ))
+    .map(|(s, parts, q, f)| url_parts(Some(s), parts, q, f))
+    .parse(input)
+}
+
+// 和uri_reference相同, 保留各个部分
+pub fn parsed_url(input: &str) -> IResult<&str, ParsedUrl> {
+    alt((
+        parsed_uri,
+        tuple((
+            relative_part_parts,
+            opt(preceded(nomchar('?'), query)),
+            opt(preceded(nomchar('#'), fragment)),
+        ))
+        .map(|(parts, q, f)| url_parts(None, parts, q, f)),
+    ))(input)
+}
+
 pub fn absolute_uri(input: &str) -> IResult<&str, &str> {
     recognize(tuple((
         scheme,
@@ -0,0 +1,461 @@
+//! 解析[requirements file](https://pip.pypa.io/en/stable/reference/requirements-file-format/)
+//! 处理流程参考pip的req_file.py: 先合并以'\'结尾的续行(join_lines), 再去掉注释(ignore_comments), 最后逐行解析
+use std::{collections::HashMap, ops::Range};
+
+use nom::{
+    branch::alt,
+    bytes::complete::{tag, take_till1, take_while1},
+    character::complete::{char as nomchar, hex_digit1, satisfy, space0, space1},
+    combinator::{cut, eof, map_opt, opt, peek, recognize, verify},
+    multi::{many0, many1},
+    sequence::{delimited, preceded, terminated, tuple},
+    Finish, Parser,
+};
+
+use super::{
+    requirement_specifier::{extras, parsed_uri, quoted_marker, specification},
+    vcs_url::vcs_url,
+};
+use crate::{
+    error::{expect, IResult, ParseError},
+    requirements::{
+        hashes::HashAlgorithm, url::ParsedUrl, ExtraName, LineContent, PipOption, ProjectLocation,
+        ProjectRequirement, Requirement, RequirementsFile, RequirementsFileLine, Unexpanded,
+        PIP_OPTIONS,
+    },
+};
+
+pub fn option_value(input: &str) -> IResult<&str, &str> {
+    take_till1(|c: char| c.is_whitespace())(input)
+}
+
+// <algorithm>:<hex digest>, 如 sha256:2cf24d...
+// pip只允许STRONG_HASHES, 见pip中req_file.py的_handle_merge_hash
+pub fn hash_value(input: &str) -> IResult<&str, (HashAlgorithm, &str)> {
+    tuple((
+        map_opt(
+            take_while1(|c: char| c.is_ascii_alphanumeric()),
+            |a: &str| a.parse::<HashAlgorithm>().ok(),
+        ),
+        preceded(nomchar(':'), hex_digit1),
+    ))(input)
+}
+
+// --name=value | --name value | --flag
+pub fn long_option(input: &str) -> IResult<&str, PipOption> {
+    alt((
+        map_opt(recognize_long_name, |name| PipOption::new(name, None)),
+        long_option_with_value,
+    ))(input)
+}
+
+// 选项名确定之后不再回溯, 值的错误指向值的开头
+fn long_option_with_value(input: &str) -> IResult<&str, PipOption> {
+    let (input, name) = expect(
+        "pip option",
+        verify(recognize_long_name, |name: &str| {
+            PIP_OPTIONS
+                .iter()
+                .any(|(_, long, takes_value)| *takes_value && *long == name)
+        }),
+    )(input)?;
+    let expected = if name == "--hash" {
+        "sha256, sha384 or sha512 hash"
+    } else {
+        "option value"
+    };
+    cut(preceded(
+        expect(expected, alt((tag("="), space1))),
+        expect(
+            expected,
+            map_opt(option_value, |v| PipOption::new(name, Some(v))),
+        ),
+    ))(input)
+}
+
+fn recognize_long_name(input: &str) -> IResult<&str, &str> {
+    recognize(tuple((
+        tag("--"),
+        take_while1(|c: char| c.is_ascii_alphanumeric() || c == '-' || c == '_'),
+    )))(input)
+}
+
+// -xvalue | -x value, pip的短选项都是带值的
+pub fn short_option(input: &str) -> IResult<&str, PipOption> {
+    let (input, name) = expect(
+        "pip option",
+        map_opt(
+            preceded(nomchar('-'), satisfy(|c| c.is_ascii_alphabetic())),
+            |c| {
+                PIP_OPTIONS
+                    .iter()
+                    .find(|(short, _, _)| *short == Some(c))
+                    .map(|(_, long, _)| *long)
+            },
+        ),
+    )(input)?;
+    cut(preceded(
+        space0,
+        expect(
+            "option value",
+            map_opt(option_value, |v| PipOption::new(name, Some(v))),
+        ),
+    ))(input)
+}
+
+// 选项之后必须是空白或者行尾, 如 --pre=1 是错误
+pub fn pip_option(input: &str) -> IResult<&str, PipOption> {
+    terminated(
+        alt((long_option, short_option)),
+        cut(expect("whitespace", peek(alt((space1, eof))))),
+    )(input)
+}
+
+pub fn pip_options(input: &str) -> IResult<&str, Vec<PipOption>> {
+    many1(terminated(pip_option, space0))(input)
+}
+
+pub fn archive_url(input: &str) -> IResult<&str, ParsedUrl> {
+    terminated(parsed_uri, peek(alt((space1, eof))))(input)
+}
+
+// 形如"scheme:"的url, 只有一个字母的scheme视为windows的盘符
+pub fn url_scheme(s: &str) -> Option<&str> {
+    let (scheme, _) = s.split_once(':')?;
+    (scheme.len() > 1
+        && scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+        && scheme
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c)))
+    .then_some(scheme)
+}
+
+// 见pip中constructors.py的_looks_like_path, 以'-'开头的是选项
+pub fn looks_like_path(s: &str) -> bool {
+    !s.starts_with('-')
+        && url_scheme(s).is_none()
+        && (s.starts_with('.') || s.contains('/') || s.contains('\\'))
+}
+
+// <path>[extras], 见pip中constructors.py的_strip_extras
+pub fn project_path(input: &str) -> IResult<&str, (ProjectLocation, Vec<ExtraName>)> {
+    tuple((
+        verify(
+            take_till1(|c: char| c.is_whitespace() || c == ';' || c == '['),
+            looks_like_path,
+        ),
+        opt(extras),
+    ))
+    .map(|(path, extras)| {
+        (
+            ProjectLocation::Path(path.to_string()),
+            extras.flatten().unwrap_or_default(),
+        )
+    })
+    .parse(input)
+}
+
+fn is_file_url(url: &ParsedUrl) -> bool {
+    url.scheme
+        .as_deref()
+        .is_some_and(|scheme| scheme.eq_ignore_ascii_case("file"))
+}
+
+// 后面要有空白, 否则可能只是url的前一部分
+fn vcs_location(input: &str) -> IResult<&str, (ProjectLocation, Vec<ExtraName>)> {
+    terminated(vcs_url, peek(alt((space1, eof))))
+        .map(|u| (ProjectLocation::Vcs(u), vec![]))
+        .parse(input)
+}
+
+pub fn local_project_location(input: &str) -> IResult<&str, (ProjectLocation, Vec<ExtraName>)> {
+    alt((
+        vcs_location,
+        verify(archive_url, is_file_url).map(|u| (ProjectLocation::Url(u), vec![])),
+        project_path,
+    ))(input)
+}
+
+// 本地目录或者file: url, 可以带marker
+pub fn local_project_path(input: &str) -> IResult<&str, ProjectRequirement> {
+    tuple((local_project_location, opt(preceded(space0, quoted_marker))))
+        .map(|((location, extras), marker_expr)| ProjectRequirement {
+            location,
+            extras,
+            editable: false,
+            marker_expr,
+        })
+        .parse(input)
+}
+
+// -e <value> | -e<value> | --editable=<value> | --editable <value>
+pub fn editable_flag(input: &str) -> IResult<&str, &str> {
+    alt((
+        terminated(tag("--editable"), alt((tag("="), space1))),
+        terminated(tag("-e"), space0),
+    ))(input)
+}
+
+// 可编辑安装只支持本地目录, file: url和VCS url
+// 见pip中constructors.py的parse_editable
+pub fn editable_location(input: &str) -> IResult<&str, (ProjectLocation, Vec<ExtraName>)> {
+    alt((
+        vcs_location,
+        verify(archive_url, is_file_url).map(|u| (ProjectLocation::Url(u), vec![])),
+        terminated(project_path, peek(alt((space1, eof)))),
+    ))(input)
+}
+
+pub fn editable_project(input: &str) -> IResult<&str, ProjectRequirement> {
+    preceded(
+        editable_flag,
+        expect("local project path or VCS URL", editable_location),
+    )
+    .map(|(location, extras)| ProjectRequirement {
+        location,
+        extras,
+        editable: true,
+        marker_expr: None,
+    })
+    .parse(input)
+}
+
+pub fn requirement(input: &str) -> IResult<&str, Requirement> {
+    alt((
+        editable_project.map(Requirement::Project),
+        local_project_path.map(Requirement::Project),
+        archive_url.map(Requirement::ArchiveUrl),
+        specification.map(Requirement::Specifier),
+    ))(input)
+}
+
+// 注释已经去掉的逻辑行
+pub fn line_content(input: &str) -> IResult<&str, LineContent> {
+    preceded(
+        space0,
+        alt((
+            eof.map(|_| LineContent::Empty),
+            terminated(
+                tuple((
+                    terminated(requirement, space0),
+                    many0(terminated(pip_option, space0)),
+                )),
+                expect("pip option", eof),
+            )
+            .map(|(r, o)| LineContent::Requirement(r, o)),
+            terminated(pip_options, eof).map(LineContent::Options),
+        )),
+    )(input)
+}
+
+// COMMENT_RE = re.compile(r"(^|\s+)#.*$")
+pub fn is_comment_line(line: &str) -> bool {
+    line.trim_start().starts_with('#')
+}
+
+// 注释开头的'#'的位置, '#'在行首或者前面是空白
+pub fn comment_start(line: &str) -> Option<usize> {
+    let mut prev_is_space = true;
+    for (i, c) in line.char_indices() {
+        if c == '#' && prev_is_space {
+            return Some(i);
+        }
+        prev_is_space = c.is_whitespace();
+    }
+    None
+}
+
+// 返回(去掉注释后的内容, 注释), 注释不包括开头的'#'
+pub fn split_comment(line: &str) -> (&str, Option<&str>) {
+    match comment_start(line) {
+        Some(i) => (line[..i].trim_end(), Some(line[i + 1..].trim_end())),
+        None => (line.trim_end(), None),
+    }
+}
+
+// 合并续行后的一行
+#[derive(Debug, PartialEq)]
+pub struct LogicalLine {
+    // 起始的行号, 从1开始
+    pub line_number: usize,
+    pub text: String,
+    // 每个物理行一项: (在text中的偏移, 在原文件中的偏移)
+    pub segments: Vec<(usize, usize)>,
+}
+
+impl LogicalLine {
+    // 把text中的偏移转换为原文件中的偏移
+    pub fn source_offset(&self, offset: usize) -> usize {
+        let (text_start, source_start) = self
+            .segments
+            .iter()
+            .rev()
+            .find(|(text_start, _)| *text_start <= offset)
+            .unwrap_or(&(0, 0));
+        source_start + (offset - text_start)
+    }
+}
+
+// 合并续行
+pub fn logical_lines(input: &str) -> Vec<LogicalLine> {
+    let mut lines = vec![];
+    let mut new_line: Option<LogicalLine> = None;
+    let mut source_offset = 0;
+    for (i, raw) in input.split_inclusive('\n').enumerate() {
+        let line_number = i + 1;
+        let line = raw.trim_end_matches(['\n', '\r']);
+        if !line.ends_with('\\') || is_comment_line(line) {
+            match new_line.take() {
+                Some(mut joined) => {
+                    // 保证注释在合并之后仍能被识别
+                    if is_comment_line(line) {
+                        joined.text.push(' ');
+                    }
+                    joined.segments.push((joined.text.len(), source_offset));
+                    joined.text.push_str(line);
+                    lines.push(joined);
+                }
+                None => lines.push(LogicalLine {
+                    line_number,
+                    text: line.to_string(),
+                    segments: vec![(0, source_offset)],
+                }),
+            }
+        } else {
+            let joined = new_line.get_or_insert_with(|| LogicalLine {
+                line_number,
+                text: String::new(),
+                segments: vec![],
+            });
+            joined.segments.push((joined.text.len(), source_offset));
+            joined.text.push_str(line.trim_end_matches('\\'));
+        }
+        source_offset += raw.len();
+    }
+    // 最后一行以'\'结尾
+    if let Some(joined) = new_line {
+        lines.push(joined);
+    }
+    lines
+}
+
+// ${VAR}形式的环境变量, 见pip中req_file.py的expand_env_variables
+// 变量名只能是大写字母, 数字和'_'; pip中没有定义或者值为空的变量保留原样
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct EnvVars {
+    pub vars: HashMap<String, String>,
+    // 为true时没有定义(或者值为空)的变量是错误
+    pub strict: bool,
+}
+
+pub fn env_var_ref(input: &str) -> IResult<&str, &str> {
+    delimited(
+        tag("${"),
+        take_while1(|c: char| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_'),
+        nomchar('}'),
+    )(input)
+}
+
+// 展开之后的文本
+#[derive(Debug, PartialEq)]
+pub struct Expanded {
+    pub text: String,
+    // 每个替换: (在text中的范围, 在原文本中的范围)
+    pub replacements: Vec<(Range<usize>, Range<usize>)>,
+}
+
+impl Expanded {
+    // 把text中的偏移转换为原文本中的偏移, 落在替换的值里时取变量引用的开头
+    pub fn original_offset(&self, offset: usize) -> usize {
+        let mut original_offset = offset;
+        for (expanded, original) in &self.replacements {
+            if offset < expanded.start {
+                break;
+            }
+            if offset < expanded.end {
+                return original.start;
+            }
+            original_offset = original.end + (offset - expanded.end);
+        }
+        original_offset
+    }
+}
+
+// 出错时返回未定义的变量在原文本中的偏移
+pub fn expand_env_vars(text: &str, env: &EnvVars) -> Result<Expanded, usize> {
+    let mut expanded = Expanded {
+        text: String::with_capacity(text.len()),
+        replacements: vec![],
+    };
+    let mut copied = 0;
+    let mut search = 0;
+    while let Some(i) = text[search..].find("${").map(|i| search + i) {
+        search = i + 2;
+        let Ok((rest, name)) = env_var_ref(&text[i..]) else {
+            continue;
+        };
+        let end = text.len() - rest.len();
+        match env.vars.get(name).filter(|v| !v.is_empty()) {
+            Some(value) => {
+                expanded.text.push_str(&text[copied..i]);
+                let start = expanded.text.len();
+                expanded.text.push_str(value);
+                expanded
+                    .replacements
+                    .push((start..expanded.text.len(), i..end));
+                copied = end;
+            }
+            None if env.strict => return Err(i),
+            None => {}
+        }
+        search = end;
+    }
+    expanded.text.push_str(&text[copied..]);
+    Ok(expanded)
+}
+
+pub fn requirements_file(input: &str) -> Result<RequirementsFile, ParseError> {
+    requirements_file_with_env(input, &EnvVars::default())
+}
+
+// 和pip一样, 在合并续行和去掉注释之后展开环境变量
+pub fn requirements_file_with_env(
+    input: &str,
+    env: &EnvVars,
+) -> Result<RequirementsFile, ParseError> {
+    let mut lines = vec![];
+    for logical_line in logical_lines(input) {
+        let (content, comment) = split_comment(&logical_line.text);
+        let expanded = expand_env_vars(content, env).map_err(|offset| {
+            ParseError::new(
+                input,
+                logical_line.source_offset(offset),
+                "defined environment variable",
+            )
+        })?;
+        match line_content(&expanded.text).finish() {
+            Ok((_, line_content)) => lines.push(RequirementsFileLine {
+                line_number: logical_line.line_number,
+                unexpanded: (!expanded.replacements.is_empty()).then(|| Unexpanded {
+                    text: content.to_string(),
+                    expanded: line_content.to_string(),
+                }),
+                content: line_content,
+                comment: comment.map(|c| c.to_string()),
+            }),
+            Err(e) => {
+                let expected = match e.expected {
+                    "" => "requirement or pip option",
+                    expected => expected,
+                };
+                let offset = expanded.original_offset(expanded.text.len() - e.input.len());
+                return Err(ParseError::new(
+                    input,
+                    logical_line.source_offset(offset),
+                    expected,
+                ));
+            }
+        }
+    }
+    Ok(RequirementsFile { lines })
+}
@@ -0,0 +1,58 @@
+//! 解析sdist文件名: {name}-{version}{extension}, 如 foo_bar-1.2.3.post1.tar.gz
+//! 旧的sdist中名字可能带'-'或者数字, 从左到右找第一个能把剩下的部分解析为版本号的'-'
+use nom::combinator::all_consuming;
+
+use super::{requirement_specifier::identifier, version::version_scheme};
+use crate::{
+    error::{Expected, IResult},
+    requirements::{
+        sdist::{SdistFilename, SdistFormat},
+        PackageName, Version,
+    },
+};
+
+// 按位置拆分为(名字, 版本号), i是'-'的位置
+fn split_at_dash(stem: &str, i: usize) -> Option<(String, Version)> {
+    let (_, name) = all_consuming(identifier)(&stem[..i]).ok()?;
+    let (_, version) = all_consuming(version_scheme)(&stem[i + 1..]).ok()?;
+    Some((name, version))
+}
+
+// name为None时取第一个合法的拆分, 否则要求名字规范化之后和name相同
+pub fn split_sdist_filename(s: &str, name: Option<&PackageName>) -> Option<SdistFilename> {
+    let (stem, format) = SdistFormat::split_extension(s)?;
+    stem.match_indices('-').find_map(|(i, _)| {
+        let (n, version) = split_at_dash(stem, i)?;
+        let n = PackageName::new(n);
+        name.is_none_or(|name| *name == n).then_some(SdistFilename {
+            name: n,
+            version,
+            format,
+        })
+    })
+}
+
+// 整个输入是一个文件名
+pub fn sdist_filename(input: &str) -> IResult<&str, SdistFilename> {
+    match split_sdist_filename(input, None) {
+        Some(sdist) => Ok((&input[input.len()..], sdist)),
+        None => Err(nom::Err::Error(sdist_error(input))),
+    }
+}
+
+// 没有合法的拆分时, 名字的错误指向开头, 版本号的错误指向第一个'-'之后
+fn sdist_error(input: &str) -> Expected<&str> {
+    let Some((stem, _)) = SdistFormat::split_extension(input) else {
+        return Expected::new(
+            &input[input.len()..],
+            "'.tar.gz', '.zip', '.tar.bz2' or '.tgz'",
+        );
+    };
+    let Some(i) = stem.find('-') else {
+        return Expected::new(&input[stem.len()..], "'-'");
+    };
+    if all_consuming(identifier)(&stem[..i]).is_err() {
+        return Expected::new(input, "project name");
+    }
+    Expected::new(&input[i + 1..], "version")
+}
@@ -0,0 +1,78 @@
+//! 解析VCS url, 如 git+https://github.com/pypa/pip.git@22.0#egg=pip&subdirectory=src
+//! 拆分的方式参考pip中vcs/versioncontrol.py的get_url_rev_and_auth
+use nom::combinator::map_opt;
+
+use super::requirement_specifier::uri;
+use crate::{
+    error::IResult,
+    requirements::{VcsUrl, VersionControlSystem},
+};
+
+// 按'&'拆分fragment, 没有'='的参数值为空字符串
+fn fragment_params(fragment: &str) -> Vec<(String, String)> {
+    fragment
+        .split('&')
+        .filter(|p| !p.is_empty())
+        .map(|p| match p.split_once('=') {
+            Some((k, v)) => (k.to_string(), v.to_string()),
+            None => (p.to_string(), String::new()),
+        })
+        .collect()
+}
+
+// 输入是一个完整的uri
+pub fn split_vcs_url(s: &str) -> Option<VcsUrl> {
+    let (scheme, rest) = s.split_once(':')?;
+    let (prefix, transport) = scheme.split_once('+')?;
+    let vcs = VersionControlSystem::from_prefix(prefix);
+    if vcs == VersionControlSystem::Unknown || transport.is_empty() {
+        return None;
+    }
+    let (rest, fragment) = match rest.split_once('#') {
+        Some((rest, fragment)) => (rest, Some(fragment)),
+        None => (rest, None),
+    };
+    let (rest, query) = match rest.split_once('?') {
+        Some((rest, query)) => (rest, Some(query)),
+        None => (rest, None),
+    };
+    // netloc中的'@'是用户信息, 只有path中最后一个'@'之后的是rev
+    let path_start = match rest.strip_prefix("//") {
+        Some(authority) => 2 + authority.find('/').unwrap_or(authority.len()),
+        None => 0,
+    };
+    let (base, rev) = match rest[path_start..].rfind('@') {
+        // pip: The URL has an empty revision
+        Some(i) if path_start + i + 1 == rest.len() => return None,
+        Some(i) => (
+            &rest[..path_start + i],
+            Some(rest[path_start + i + 1..].to_string()),
+        ),
+        None => (rest, None),
+    };
+    let mut url = format!("{}:{}", transport, base);
+    if let Some(query) = query {
+        url.push('?');
+        url.push_str(query);
+    }
+    let mut vcs_url = VcsUrl {
+        vcs,
+        url,
+        rev,
+        egg: None,
+        subdirectory: None,
+        params: vec![],
+    };
+    for (key, value) in fragment_params(fragment.unwrap_or("")) {
+        match key.as_str() {
+            "egg" if vcs_url.egg.is_none() => vcs_url.egg = Some(value),
+            "subdirectory" if vcs_url.subdirectory.is_none() => vcs_url.subdirectory = Some(value),
+            _ => vcs_url.params.push((key, value)),
+        }
+    }
+    Some(vcs_url)
+}
+
+pub fn vcs_url(input: &str) -> IResult<&str, VcsUrl> {
+    map_opt(uri, split_vcs_url)(input)
+}
@@ -9,10 +9,13 @@ use nom::{
     combinator::opt,
     multi::many0,
     sequence::{preceded, terminated, tuple},
-    IResult, Parser,
+    Parser,
 };
 
-use crate::requirements::{LocalVersionPart, Version};
+use crate::{
+    error::IResult,
+    requirements::{LocalVersionPart, Version},
+};
 
 pub fn epoch(input: &str) -> IResult<&str, u64> {
     terminated(digit1, nomchar('!'))
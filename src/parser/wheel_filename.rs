@@ -0,0 +1,81 @@
+//! 解析wheel文件名: {name}-{version}(-{build})?-{python tag}-{abi tag}-{platform tag}.whl
+//! 见<https://packaging.python.org/en/latest/specifications/binary-distribution-format/#file-name-convention>
+//! 和packaging中utils.py的parse_wheel_filename一样先按'-'拆分, 再分别检查各部分
+use nom::{
+    character::complete::digit1,
+    combinator::{all_consuming, map_res, rest},
+    sequence::tuple,
+    Offset, Parser,
+};
+
+use super::{requirement_specifier::identifier, version::version_scheme};
+use crate::{
+    error::{Expected, IResult},
+    requirements::{
+        wheel::{Tag, WheelFilename},
+        PackageName,
+    },
+};
+
+// 构建号以数字开头, 如 1 或者 1local; 比较时先比较数字, 再比较后面的字符串
+pub fn build_tag(input: &str) -> IResult<&str, (u64, String)> {
+    tuple((map_res(digit1, str::parse), rest))
+        .map(|(n, s): (u64, &str)| (n, s.to_string()))
+        .parse(input)
+}
+
+// 压缩的tag集合, 如 py2.py3-none-any, 展开为每个组合; 和packaging一样统一为小写
+pub fn expand_tags(python: &str, abi: &str, platform: &str) -> Vec<Tag> {
+    let mut tags = vec![];
+    for python in python.split('.') {
+        for abi in abi.split('.') {
+            for platform in platform.split('.') {
+                let tag = Tag::new(python, abi, platform);
+                if !tags.contains(&tag) {
+                    tags.push(tag);
+                }
+            }
+        }
+    }
+    tags
+}
+
+// 先按'-'拆分, 再检查各部分; 出错时返回(出错处剩余的输入, 期望的token)
+fn split_wheel_filename(input: &str) -> Result<WheelFilename, (&str, &'static str)> {
+    let Some(stem) = input.strip_suffix(".whl") else {
+        return Err((&input[input.len()..], "'.whl'"));
+    };
+    let parts: Vec<&str> = stem.split('-').collect();
+    let (name, version, build, python, abi, platform) = match parts[..] {
+        [name, version, python, abi, platform] => (name, version, None, python, abi, platform),
+        [name, version, build, python, abi, platform] => {
+            (name, version, Some(build), python, abi, platform)
+        }
+        _ => return Err((input, "name-version[-build]-python-abi-platform.whl")),
+    };
+    if parts.iter().any(|p| p.is_empty()) {
+        return Err((input, "name-version[-build]-python-abi-platform.whl"));
+    }
+    let at = |part: &str| &input[input.offset(part)..];
+    let (_, name) = all_consuming(identifier)(name).map_err(|_| (at(name), "project name"))?;
+    let (_, version) =
+        all_consuming(version_scheme)(version).map_err(|_| (at(version), "version"))?;
+    let build = match build {
+        Some(build) => Some(build_tag(build).map_err(|_| (at(build), "build tag"))?.1),
+        None => None,
+    };
+    Ok(WheelFilename {
+        name: PackageName::new(name),
+        version,
+        build,
+        tags: expand_tags(python, abi, platform),
+    })
+}
+
+// 整个输入是一个文件名
+pub fn wheel_filename(input: &str) -> IResult<&str, WheelFilename> {
+    match split_wheel_filename(input) {
+        Ok(wheel) => Ok((&input[input.len()..], wheel)),
+        Err((at, expected)) => Err(nom::Err::Error(Expected::new(at, expected))),
+    }
+}
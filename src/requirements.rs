@@ -1,9 +1,34 @@
-use std::{cmp::Ordering, fmt::Display};
+use std::{cmp::Ordering, collections::BTreeSet, fmt::Display, str::FromStr};
 
-use crate::parser::version::version_scheme;
+use nom::{character::complete::space0, sequence::preceded};
 
+use self::{hashes::HashAlgorithm, url::ParsedUrl};
+use crate::{
+    error::{expect, parse_complete, ParseError},
+    parser::{
+        lenient::{lenient_specification, LenientWarning},
+        requirement_specifier::{identifier, marker_or, specification, version_one, versionspec},
+        requirements_file::{hash_value, requirements_file, requirements_file_with_env, EnvVars},
+        vcs_url::vcs_url,
+        version::version_scheme,
+    },
+};
+
+pub mod cst;
+pub mod hashes;
+pub mod loader;
+pub mod metadata;
+#[cfg(feature = "toml")]
+pub mod pyproject;
+pub mod range;
+pub mod requires_python;
+pub mod sdist;
+#[cfg(feature = "serde")]
+pub mod serialization;
 #[cfg(test)]
 mod tests;
+pub mod url;
+pub mod wheel;
 
 // version_cmp
 #[derive(Debug, PartialEq, Clone, Copy)]
@@ -36,18 +61,79 @@ impl TryFrom<&str> for Comparison {
     }
 }
 
+impl Comparison {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::LessThan => "<",
+            Self::LessThanOrEqual => "<=",
+            Self::NotEqual => "!=",
+            Self::Equal => "==",
+            Self::GreaterThanOrEqual => ">=",
+            Self::GreaterThan => ">",
+            Self::CompatibleRelease => "~=",
+            Self::ArbitraryEqual => "===",
+        }
+    }
+}
+
+impl Display for Comparison {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for Comparison {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let op = s.trim();
+        Self::try_from(op).map_err(|_| ParseError::at(s, s.trim_start(), "version operator"))
+    }
+}
+
 // marker_op
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub enum MarkerOp {
     Comparison(Comparison),
     In,
     NotIn,
 }
 
+// marker中比较的一边: 环境变量名, 或者带引号的字符串(不含引号)
+// 需要区分, 否则 'python_version' == "3.10" 中的字符串会被当作变量
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum MarkerValue {
+    Variable(String),
+    Literal(String),
+}
+
+impl MarkerValue {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Variable(s) | Self::Literal(s) => s,
+        }
+    }
+
+    pub fn is_extra(&self) -> bool {
+        matches!(self, Self::Variable(v) if v == "extra")
+    }
+}
+
+// 变量名原样输出, 字符串加引号; python_str中不能转义, 值中有'"'时用单引号
+impl Display for MarkerValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Variable(var) => write!(f, "{}", var),
+            Self::Literal(s) if s.contains('"') => write!(f, "'{}'", s),
+            Self::Literal(s) => write!(f, "\"{}\"", s),
+        }
+    }
+}
+
 // and 优先级大于 or
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub enum MarkerExpr {
-    Basic(String, MarkerOp, String),
+    Basic(MarkerValue, MarkerOp, MarkerValue),
     And(Box<Self>, Box<Self>),
     Or(Box<Self>, Box<Self>),
 }
@@ -58,7 +144,188 @@ impl From<Comparison> for MarkerOp {
     }
 }
 
-#[derive(Debug, PartialEq)]
+impl MarkerOp {
+    // refer to _eval_op at https://github.com/pypa/packaging/blob/main/src/packaging/markers.py
+    // 两边都能解析为version时按version比较, 否则按字符串比较
+    pub fn evaluate(&self, lhs: &str, rhs: &str) -> bool {
+        match self {
+            Self::In => rhs.contains(lhs),
+            Self::NotIn => !rhs.contains(lhs),
+            Self::Comparison(c) => {
+                if let (Ok(("", spec)), Ok(("", _))) = (
+                    version_one(&format!("{}{}", c.as_str(), rhs)),
+                    version_scheme(lhs),
+                ) {
+                    return spec.contains(lhs);
+                }
+                match c {
+                    Comparison::LessThan => lhs < rhs,
+                    Comparison::LessThanOrEqual => lhs <= rhs,
+                    Comparison::NotEqual => lhs != rhs,
+                    Comparison::Equal | Comparison::ArbitraryEqual => lhs == rhs,
+                    Comparison::GreaterThanOrEqual => lhs >= rhs,
+                    Comparison::GreaterThan => lhs > rhs,
+                    // 字符串没有compatible release的语义
+                    Comparison::CompatibleRelease => false,
+                }
+            }
+        }
+    }
+}
+
+impl Display for MarkerOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Comparison(c) => write!(f, "{}", c),
+            Self::In => write!(f, "in"),
+            Self::NotIn => write!(f, "not in"),
+        }
+    }
+}
+
+impl FromStr for MarkerOp {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_whitespace().collect::<Vec<_>>()[..] {
+            ["in"] => Ok(Self::In),
+            ["not", "in"] => Ok(Self::NotIn),
+            _ => s
+                .parse::<Comparison>()
+                .map(Self::Comparison)
+                .map_err(|_| ParseError::at(s, s.trim_start(), "marker operator")),
+        }
+    }
+}
+
+// 只在优先级需要时加括号: and中的or, 以及右边和自己相同的操作(解析时是左结合的)
+impl Display for MarkerExpr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let operand = |f: &mut std::fmt::Formatter<'_>, e: &Self, parens: bool| {
+            if parens {
+                write!(f, "({})", e)
+            } else {
+                write!(f, "{}", e)
+            }
+        };
+        match self {
+            Self::Basic(lhs, op, rhs) => write!(f, "{} {} {}", lhs, op, rhs),
+            Self::And(lhs, rhs) => {
+                operand(f, lhs, matches!(**lhs, Self::Or(..)))?;
+                write!(f, " and ")?;
+                operand(f, rhs, !matches!(**rhs, Self::Basic(..)))
+            }
+            Self::Or(lhs, rhs) => {
+                operand(f, lhs, false)?;
+                write!(f, " or ")?;
+                operand(f, rhs, matches!(**rhs, Self::Or(..)))
+            }
+        }
+    }
+}
+
+impl MarkerExpr {
+    // extras为空时extra取"", 否则只要有一个extra满足即可, 和pip中的match_markers一致
+    pub fn evaluate<S: AsRef<str>>(&self, env: &MarkerEnvironment, extras: &[S]) -> bool {
+        if extras.is_empty() {
+            self.evaluate_with_extra(env, "")
+        } else {
+            extras
+                .iter()
+                .any(|extra| self.evaluate_with_extra(env, extra.as_ref()))
+        }
+    }
+
+    fn evaluate_with_extra(&self, env: &MarkerEnvironment, extra: &str) -> bool {
+        match self {
+            // PEP 685: 比较extra时两边都要规范化
+            Self::Basic(lhs, op, rhs) if lhs.is_extra() || rhs.is_extra() => op.evaluate(
+                &normalize_name(env.resolve(lhs, extra)),
+                &normalize_name(env.resolve(rhs, extra)),
+            ),
+            Self::Basic(lhs, op, rhs) => {
+                op.evaluate(env.resolve(lhs, extra), env.resolve(rhs, extra))
+            }
+            Self::And(lhs, rhs) => {
+                lhs.evaluate_with_extra(env, extra) && rhs.evaluate_with_extra(env, extra)
+            }
+            Self::Or(lhs, rhs) => {
+                lhs.evaluate_with_extra(env, extra) || rhs.evaluate_with_extra(env, extra)
+            }
+        }
+    }
+}
+
+impl FromStr for MarkerExpr {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_complete(s, marker_or, "'and', 'or' or end of input")
+    }
+}
+
+// env_var中除了extra以外的变量, 见 https://peps.python.org/pep-0508/#environment-markers
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct MarkerEnvironment {
+    pub implementation_name: String,
+    pub implementation_version: String,
+    pub os_name: String,
+    pub platform_machine: String,
+    pub platform_release: String,
+    pub platform_system: String,
+    pub platform_version: String,
+    pub python_full_version: String,
+    pub platform_python_implementation: String,
+    pub python_version: String,
+    pub sys_platform: String,
+}
+
+impl MarkerEnvironment {
+    pub fn get(&self, name: &str) -> Option<&str> {
+        match name {
+            "implementation_name" => Some(&self.implementation_name),
+            "implementation_version" => Some(&self.implementation_version),
+            "os_name" => Some(&self.os_name),
+            "platform_machine" => Some(&self.platform_machine),
+            "platform_release" => Some(&self.platform_release),
+            "platform_system" => Some(&self.platform_system),
+            "platform_version" => Some(&self.platform_version),
+            "python_full_version" => Some(&self.python_full_version),
+            "platform_python_implementation" => Some(&self.platform_python_implementation),
+            "python_version" => Some(&self.python_version),
+            "sys_platform" => Some(&self.sys_platform),
+            _ => None,
+        }
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut String> {
+        match name {
+            "implementation_name" => Some(&mut self.implementation_name),
+            "implementation_version" => Some(&mut self.implementation_version),
+            "os_name" => Some(&mut self.os_name),
+            "platform_machine" => Some(&mut self.platform_machine),
+            "platform_release" => Some(&mut self.platform_release),
+            "platform_system" => Some(&mut self.platform_system),
+            "platform_version" => Some(&mut self.platform_version),
+            "python_full_version" => Some(&mut self.python_full_version),
+            "platform_python_implementation" => Some(&mut self.platform_python_implementation),
+            "python_version" => Some(&mut self.python_version),
+            "sys_platform" => Some(&mut self.sys_platform),
+            _ => None,
+        }
+    }
+
+    // 变量取环境中的值(extra取当前的extra, 未知的变量为空字符串), 字符串取原来的值
+    fn resolve<'a>(&'a self, value: &'a MarkerValue, extra: &'a str) -> &'a str {
+        match value {
+            MarkerValue::Variable(var) if var == "extra" => extra,
+            MarkerValue::Variable(var) => self.get(var).unwrap_or(""),
+            MarkerValue::Literal(s) => s,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum VersionControlSystem {
     Git,
     Mercurial,
@@ -67,157 +334,949 @@ pub enum VersionControlSystem {
     Unknown,
 }
 
+impl VersionControlSystem {
+    // url scheme中'+'前边的部分, 如git+https中的git
+    pub fn from_prefix(prefix: &str) -> Self {
+        match prefix.to_ascii_lowercase().as_str() {
+            "git" => Self::Git,
+            "hg" => Self::Mercurial,
+            "svn" => Self::Subversion,
+            "bzr" => Self::Bazaar,
+            _ => Self::Unknown,
+        }
+    }
+}
+
+impl Display for VersionControlSystem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let prefix = match self {
+            Self::Git => "git",
+            Self::Mercurial => "hg",
+            Self::Subversion => "svn",
+            Self::Bazaar => "bzr",
+            Self::Unknown => "unknown",
+        };
+        write!(f, "{}", prefix)
+    }
+}
+
+// <vcs>+<url>[@<rev>][#egg=<name>&subdirectory=<path>...], 如 git+https://github.com/pypa/pip.git@22.0#egg=pip
+// 见pip中vcs/versioncontrol.py的get_url_rev_and_auth和models/link.py; 解析时不接受Unknown
+#[derive(Debug, PartialEq, Clone)]
+pub struct VcsUrl {
+    pub vcs: VersionControlSystem,
+    // 去掉了vcs前缀, rev和fragment的url, 如 https://github.com/pypa/pip.git
+    pub url: String,
+    // branch, tag或者commit
+    pub rev: Option<String>,
+    pub egg: Option<String>,
+    pub subdirectory: Option<String>,
+    // fragment中其他的参数, 保持原来的顺序, 如 sha256=...
+    pub params: Vec<(String, String)>,
+}
+
+// rev写在path之后, query之前
+impl Display for VcsUrl {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (url, query) = match self.url.split_once('?') {
+            Some((url, query)) => (url, Some(query)),
+            None => (self.url.as_str(), None),
+        };
+        write!(f, "{}+{}", self.vcs, url)?;
+        if let Some(rev) = &self.rev {
+            write!(f, "@{}", rev)?;
+        }
+        if let Some(query) = query {
+            write!(f, "?{}", query)?;
+        }
+        let params = self
+            .egg
+            .iter()
+            .map(|egg| ("egg", egg.as_str()))
+            .chain(
+                self.subdirectory
+                    .iter()
+                    .map(|s| ("subdirectory", s.as_str())),
+            )
+            .chain(self.params.iter().map(|(k, v)| (k.as_str(), v.as_str())));
+        let mut sep = '#';
+        for (key, value) in params {
+            write!(f, "{}{}", sep, key)?;
+            if !value.is_empty() {
+                write!(f, "={}", value)?;
+            }
+            sep = '&';
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for VcsUrl {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_complete(
+            s,
+            preceded(space0, expect("VCS URL", vcs_url)),
+            "end of input",
+        )
+    }
+}
+
+// ===以外的版本在解析时就转换为Version, 匹配时不用重复解析
+#[derive(Debug, Clone)]
+pub enum VersionPattern {
+    // ===后边的任意字符串, 不要求符合PEP 440
+    Arbitrary(String),
+    // wildcard: 以".*"结尾, 表示前缀匹配, 这时version只有epoch和release
+    // 按语法只有==和!=允许wildcard和local version
+    Version { version: Version, wildcard: bool },
+}
+
+// 前缀匹配和~=中release末尾的0是有意义的, 如 ==1.0.* 和 ==1.* 不同
+impl PartialEq for VersionPattern {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Arbitrary(a), Self::Arbitrary(b)) => a == b,
+            (
+                Self::Version {
+                    version: a,
+                    wildcard: wa,
+                },
+                Self::Version {
+                    version: b,
+                    wildcard: wb,
+                },
+            ) => wa == wb && a == b && (!wa || a.release == b.release),
+            _ => false,
+        }
+    }
+}
+
+impl Display for VersionPattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Arbitrary(s) => write!(f, "{}", s),
+            Self::Version {
+                version,
+                wildcard: false,
+            } => write!(f, "{}", version),
+            Self::Version {
+                version,
+                wildcard: true,
+            } => write!(f, "{}.*", version),
+        }
+    }
+}
+
 // see regex for VersionSpecifier at https://github.com/pypa/packaging/blob/main/src/packaging/specifiers.py
-#[derive(Debug, PartialEq)]
-pub struct VersionSpec(pub Comparison, pub String);
+#[derive(Debug, Clone)]
+pub struct VersionSpec(pub Comparison, pub VersionPattern);
 
-impl From<(Comparison, String)> for VersionSpec {
-    fn from((c, v): (Comparison, String)) -> Self {
-        Self(c, v)
+// 参考Specifier.__eq__, 除了~=以外忽略release末尾的0
+impl PartialEq for VersionSpec {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+            && self.1 == other.1
+            && match (self.0, &self.1, &other.1) {
+                (
+                    Comparison::CompatibleRelease,
+                    VersionPattern::Version { version: a, .. },
+                    VersionPattern::Version { version: b, .. },
+                ) => a.release == b.release,
+                _ => true,
+            }
+    }
+}
+
+impl Display for VersionSpec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}{}", self.0, self.1)
+    }
+}
+
+impl FromStr for VersionSpec {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_complete(s, version_one, "end of input")
     }
 }
 
 impl VersionSpec {
+    // refer to Specifier.prereleases at https://github.com/pypa/packaging/blob/main/src/packaging/specifiers.py
+    // 除了!=以外的操作符指定了pre-release, 如 >=1.0a1 或者 <2.0a1, 就默认允许pre-releases
+    pub fn prereleases(&self) -> bool {
+        match self.0 {
+            Comparison::NotEqual => false,
+            _ => match &self.1 {
+                VersionPattern::Version { version, .. } => version.is_prerelease(),
+                VersionPattern::Arbitrary(spec) => {
+                    matches!(version_scheme(spec), Ok(("", v)) if v.is_prerelease())
+                }
+            },
+        }
+    }
+
     // refer to contains at https://github.com/pypa/packaging/blob/main/src/packaging/specifiers.py
     // 该方法默认允许pre-releases
     pub fn contains(&self, version: &str) -> bool {
         if let Ok((_, v)) = version_scheme(version) {
-            match self.0 {
-                Comparison::CompatibleRelease => self.compare_compatible(&v, &self.1),
-                Comparison::Equal => self.compare_equal(&v, &self.1),
-                Comparison::NotEqual => self.compare_not_equal(&v, &self.1),
-                Comparison::LessThanOrEqual => self.compare_less_than_equal(&v, &self.1),
-                Comparison::GreaterThanOrEqual => self.compare_greater_than_equal(&v, &self.1),
-                Comparison::LessThan => self.compare_less_than(&v, &self.1),
-                Comparison::GreaterThan => self.compare_greater_than(&v, &self.1),
-                Comparison::ArbitraryEqual => self.compare_arbitrary(&v, &self.1),
-            }
+            self.matches(&v)
         } else {
             // invalid version, just return false
             false
         }
     }
 
-    // ~=2.2 is equivalent to >=2.2,==2.*
-    fn compare_compatible(&self, prospective: &Version, spec: &str) -> bool {
-        if let Ok(("", v)) = version_scheme(spec) {
-            // ignore suffix segments(only contains epoch and release)
-            self.compare_greater_than_equal(prospective, spec)
-                && self.compare_equal(prospective, &v.prefix_str())
-        } else {
-            // 按道理spec必能解析为Version，但这里做个容错
-            false
+    // 和contains相同, 但是用已经解析好的版本, 适合对大量候选版本做匹配
+    pub fn matches(&self, prospective: &Version) -> bool {
+        let (spec, wildcard) = match &self.1 {
+            VersionPattern::Arbitrary(spec) => {
+                return self.0 == Comparison::ArbitraryEqual
+                    && self.compare_arbitrary(prospective, spec);
+            }
+            VersionPattern::Version { version, wildcard } => (version, *wildcard),
+        };
+        match self.0 {
+            Comparison::CompatibleRelease => self.compare_compatible(prospective, spec),
+            Comparison::Equal => self.compare_equal(prospective, spec, wildcard),
+            Comparison::NotEqual => self.compare_not_equal(prospective, spec, wildcard),
+            Comparison::LessThanOrEqual => self.compare_less_than_equal(prospective, spec),
+            Comparison::GreaterThanOrEqual => self.compare_greater_than_equal(prospective, spec),
+            Comparison::LessThan => self.compare_less_than(prospective, spec),
+            Comparison::GreaterThan => self.compare_greater_than(prospective, spec),
+            Comparison::ArbitraryEqual => {
+                self.compare_arbitrary(prospective, &spec.canonicalize_str(false, true))
+            }
         }
     }
+
+    // ~=2.2 is equivalent to >=2.2,==2.*
+    fn compare_compatible(&self, prospective: &Version, spec: &Version) -> bool {
+        // ignore suffix segments(only contains epoch and release)
+        let prefix = Version {
+            epoch: spec.epoch,
+            release: spec.release[..spec.release.len().saturating_sub(1)].to_vec(),
+            ..Default::default()
+        };
+        self.compare_greater_than_equal(prospective, spec)
+            && self.compare_equal(prospective, &prefix, true)
+    }
     // spec中允许包含wildcard(prefix match)和local versions
-    fn compare_equal(&self, prospective: &Version, spec: &str) -> bool {
+    fn compare_equal(&self, prospective: &Version, spec: &Version, wildcard: bool) -> bool {
         // prefix matching
         // 按解析的语法, spec只能是[epoch]release.*的格式
         // 在判断prefix match忽略prospective的local segment
         // 我这里的实现跟python不同，没用version_split，是先判断epoch是否相等，再判断release
-        if spec.ends_with(".*") {
-            if let Ok(("", spec_v)) = version_scheme(&spec[..spec.len() - 2]) {
-                if prospective.epoch != spec_v.epoch {
+        if wildcard {
+            if prospective.epoch != spec.epoch {
+                return false;
+            }
+            // 0-pad the prospective version
+            // python中的_pad_version是在_version_split数组的release后边加"0"元素，使两个数组长度相同
+            for i in 0..prospective.release.len().min(spec.release.len()) {
+                if prospective.release[i] != spec.release[i] {
                     return false;
                 }
-                // 0-pad the prospective version
-                // python中的_pad_version是在_version_split数组的release后边加"0"元素，使两个数组长度相同
-                for i in 0..prospective.release.len().min(spec_v.release.len()) {
-                    if prospective.release[i] != spec_v.release[i] {
-                        return false;
-                    }
-                }
-                // prospective.release更多不用处理，因为只要前缀匹配就可以
-                // spec_v.release更多的情况, 多出来的部分必须全是0(符合python中的0-pad)
-                if spec_v.release.len() > prospective.release.len() {
-                    return spec_v.release[prospective.release.len()..spec_v.release.len()]
-                        .iter()
-                        .all(|&i| i == 0);
-                }
-                true
-            } else {
-                false
             }
+            // prospective.release更多不用处理，因为只要前缀匹配就可以
+            // spec.release更多的情况, 多出来的部分必须全是0(符合python中的0-pad)
+            spec.release
+                .iter()
+                .skip(prospective.release.len())
+                .all(|&i| i == 0)
+        } else if spec.local.is_none() {
+            // spec没有local version时, 忽略prospective的local version
+            prospective.to_public().eq(spec)
         } else {
-            if let Ok(("", mut spec_v)) = version_scheme(spec) {
-                if spec_v.local.is_none() && prospective.local.is_some() {
-                    spec_v.local = prospective.local.clone();
-                }
-                prospective.eq(&spec_v)
-            } else {
-                false
-            }
+            prospective.eq(spec)
         }
     }
-    fn compare_not_equal(&self, prospective: &Version, spec: &str) -> bool {
-        !self.compare_equal(prospective, spec)
+    fn compare_not_equal(&self, prospective: &Version, spec: &Version, wildcard: bool) -> bool {
+        !self.compare_equal(prospective, spec, wildcard)
     }
-    fn compare_less_than_equal(&self, prospective: &Version, spec: &str) -> bool {
-        if let Ok(("", spec_v)) = version_scheme(spec) {
-            prospective.to_public() <= spec_v
-        } else {
-            false
+    fn compare_less_than_equal(&self, prospective: &Version, spec: &Version) -> bool {
+        prospective.to_public() <= *spec
+    }
+    fn compare_greater_than_equal(&self, prospective: &Version, spec: &Version) -> bool {
+        prospective.to_public() >= *spec
+    }
+    fn compare_less_than(&self, prospective: &Version, spec: &Version) -> bool {
+        if prospective >= spec {
+            return false;
         }
+        if !spec.is_prerelease()
+            && prospective.is_prerelease()
+            && prospective.to_base() == spec.to_base()
+        {
+            return false;
+        }
+        true
     }
-    fn compare_greater_than_equal(&self, prospective: &Version, spec: &str) -> bool {
-        if let Ok(("", spec_v)) = version_scheme(spec) {
-            prospective.to_public() >= spec_v
-        } else {
-            false
+    fn compare_greater_than(&self, prospective: &Version, spec: &Version) -> bool {
+        if prospective <= spec {
+            return false;
         }
+        if !spec.is_postrelease()
+            && prospective.is_postrelease()
+            && prospective.to_base() == spec.to_base()
+        {
+            return false;
+        }
+        if prospective.local.is_some() && prospective.to_base() == spec.to_base() {
+            return false;
+        }
+        true
     }
-    fn compare_less_than(&self, prospective: &Version, spec: &str) -> bool {
-        if let Ok(("", spec_v)) = version_scheme(spec) {
-            if !(prospective < &spec_v) {
-                return false;
-            }
-            if !spec_v.is_prerelease() && prospective.is_prerelease() {
-                if prospective.to_base() == spec_v.to_base() {
-                    return false;
-                }
-            }
-            true
-        } else {
-            false
+    fn compare_arbitrary(&self, prospective: &Version, spec: &str) -> bool {
+        prospective.to_string().eq_ignore_ascii_case(spec)
+    }
+}
+
+// refer to SpecifierSet at https://github.com/pypa/packaging/blob/main/src/packaging/specifiers.py
+// 和VersionSpec::contains不同, 默认不允许pre-releases
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct SpecifierSet(pub Vec<VersionSpec>);
+
+impl From<Vec<VersionSpec>> for SpecifierSet {
+    fn from(specs: Vec<VersionSpec>) -> Self {
+        Self(specs)
+    }
+}
+
+fn fmt_version_specs(f: &mut std::fmt::Formatter<'_>, specs: &[VersionSpec]) -> std::fmt::Result {
+    for (i, spec) in specs.iter().enumerate() {
+        if i > 0 {
+            write!(f, ",")?;
         }
+        write!(f, "{}", spec)?;
     }
-    fn compare_greater_than(&self, prospective: &Version, spec: &str) -> bool {
-        if let Ok(("", spec_v)) = version_scheme(spec) {
-            if !(prospective > &spec_v) {
-                return false;
+    Ok(())
+}
+
+impl Display for SpecifierSet {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fmt_version_specs(f, &self.0)
+    }
+}
+
+impl FromStr for SpecifierSet {
+    type Err = ParseError;
+
+    // 空字符串表示不限制版本
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.trim().is_empty() {
+            return Ok(Self::default());
+        }
+        parse_complete(s, versionspec, "end of input").map(Self)
+    }
+}
+
+impl SpecifierSet {
+    // 有任何一个specifier显式包含pre-release时, 默认允许pre-releases
+    pub fn prereleases(&self) -> bool {
+        self.0.iter().any(|spec| spec.prereleases())
+    }
+
+    // prereleases为None时, 由prereleases()决定
+    pub fn contains(&self, version: &str, prereleases: Option<bool>) -> bool {
+        match version_scheme(version) {
+            Ok(("", v)) => self.matches(&v, prereleases),
+            _ => false,
+        }
+    }
+
+    pub fn matches(&self, version: &Version, prereleases: Option<bool>) -> bool {
+        if version.is_prerelease() && !prereleases.unwrap_or_else(|| self.prereleases()) {
+            return false;
+        }
+        self.0.iter().all(|spec| spec.matches(version))
+    }
+
+    // 按原来的顺序返回满足条件的版本, 无法解析的版本会被忽略
+    // prereleases为None且没有specifier显式包含pre-release时, 只有在没有任何final release满足条件的情况下才返回pre-releases
+    pub fn filter<I, S>(&self, versions: I, prereleases: Option<bool>) -> Vec<S>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        // (version, is_prerelease)
+        let matched: Vec<(S, bool)> = versions
+            .into_iter()
+            .filter_map(|s| match version_scheme(s.as_ref()) {
+                Ok(("", v)) if self.matches(&v, Some(true)) => Some((s, v.is_prerelease())),
+                _ => None,
+            })
+            .collect();
+        let allow_prereleases = match prereleases {
+            Some(p) => p,
+            None => self.prereleases() || matched.iter().all(|(_, is_pre)| *is_pre),
+        };
+        matched
+            .into_iter()
+            .filter(|(_, is_pre)| allow_prereleases || !is_pre)
+            .map(|(v, _)| v)
+            .collect()
+    }
+}
+
+// PEP 503 normalization: 转为小写, 连续的'-', '_', '.'替换为一个'-'
+// see https://peps.python.org/pep-0503/#normalized-names
+pub fn normalize_name(name: &str) -> String {
+    let mut normalized = String::with_capacity(name.len());
+    let mut separator = false;
+    for c in name.chars() {
+        if matches!(c, '-' | '_' | '.') {
+            separator = true;
+            continue;
+        }
+        if separator && !normalized.is_empty() {
+            normalized.push('-');
+        }
+        separator = false;
+        normalized.push(c.to_ascii_lowercase());
+    }
+    normalized
+}
+
+// 保留原来的写法用于输出, 比较和哈希都用规范化后的名字
+#[derive(Debug, Clone, Default)]
+pub struct PackageName {
+    name: String,
+    normalized: String,
+}
+
+impl PackageName {
+    // 不做语法检查, 需要检查时用parse
+    pub fn new(name: impl Into<String>) -> Self {
+        let name = name.into();
+        let normalized = normalize_name(&name);
+        Self { name, normalized }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.name
+    }
+
+    pub fn normalized(&self) -> &str {
+        &self.normalized
+    }
+}
+
+// PEP 685中extra的规范化和PEP 503相同
+// see https://peps.python.org/pep-0685/#specification
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ExtraName(PackageName);
+
+impl ExtraName {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self(PackageName::new(name))
+    }
+
+    pub fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+
+    pub fn normalized(&self) -> &str {
+        self.0.normalized()
+    }
+}
+
+impl PartialEq for PackageName {
+    fn eq(&self, other: &Self) -> bool {
+        self.normalized == other.normalized
+    }
+}
+
+impl Eq for PackageName {}
+
+impl std::hash::Hash for PackageName {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.normalized.hash(state)
+    }
+}
+
+impl Ord for PackageName {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.normalized.cmp(&other.normalized)
+    }
+}
+
+impl PartialOrd for PackageName {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Display for PackageName {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+impl FromStr for PackageName {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_complete(
+            s,
+            preceded(space0, expect("name", identifier)),
+            "end of input",
+        )
+        .map(Self::new)
+    }
+}
+
+impl Display for ExtraName {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl FromStr for ExtraName {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse().map(Self)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct RequirementSpecifier {
+    pub name: PackageName,
+    // 按原来的顺序; 比较和输出时当作集合
+    pub extras: Vec<ExtraName>,
+    pub version_specs: Vec<VersionSpec>,
+    pub urlspec: Option<ParsedUrl>,
+    pub marker_expr: Option<MarkerExpr>,
+}
+
+// 和packaging一样把extras当作集合, 不考虑顺序和重复, 如 a[b,a] 和 a[a,b,b] 相等
+fn extras_eq(lhs: &[ExtraName], rhs: &[ExtraName]) -> bool {
+    lhs.iter().collect::<BTreeSet<_>>() == rhs.iter().collect::<BTreeSet<_>>()
+}
+
+impl PartialEq for RequirementSpecifier {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+            && extras_eq(&self.extras, &other.extras)
+            && self.version_specs == other.version_specs
+            && self.urlspec == other.urlspec
+            && self.marker_expr == other.marker_expr
+    }
+}
+
+// 和packaging中Requirement.__str__的格式一致, 如 name[a,b]>=1.0,<2; python_version < "3.8"
+// 有url时';'前要有空格, 否则';'会被当作url的一部分
+// extras按规范化的名字排序并去重, 和extras_eq一致; 为空时不输出
+fn fmt_extras(f: &mut std::fmt::Formatter<'_>, extras: &[ExtraName]) -> std::fmt::Result {
+    if extras.is_empty() {
+        return Ok(());
+    }
+    let mut extras: Vec<&ExtraName> = extras.iter().collect();
+    extras.sort();
+    extras.dedup();
+    let extras: Vec<&str> = extras.iter().map(|e| e.as_str()).collect();
+    write!(f, "[{}]", extras.join(","))
+}
+
+impl Display for RequirementSpecifier {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name)?;
+        fmt_extras(f, &self.extras)?;
+        fmt_version_specs(f, &self.version_specs)?;
+        if let Some(url) = &self.urlspec {
+            write!(f, " @ {}", url)?;
+            if self.marker_expr.is_some() {
+                write!(f, " ")?;
             }
-            if !spec_v.is_postrelease() && prospective.is_postrelease() {
-                if prospective.to_base() == spec_v.to_base() {
-                    return false;
+        }
+        if let Some(marker) = &self.marker_expr {
+            write!(f, "; {}", marker)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for RequirementSpecifier {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_complete(s, specification, "end of input")
+    }
+}
+
+impl RequirementSpecifier {
+    // name @ git+https://... 形式的VCS依赖
+    pub fn vcs_url(&self) -> Option<VcsUrl> {
+        self.urlspec
+            .as_ref()
+            .and_then(|u| u.to_string().parse().ok())
+    }
+
+    pub fn contains_version(&self, version: &str) -> bool {
+        match version_scheme(version) {
+            Ok((_, v)) => self.version_specs.iter().all(|spec| spec.matches(&v)),
+            Err(_) => false,
+        }
+    }
+
+    pub fn specifier_set(&self) -> SpecifierSet {
+        SpecifierSet(self.version_specs.clone())
+    }
+
+    // 宽松模式, 修复常见的不规范写法, 见parser::lenient
+    pub fn parse_lenient(s: &str) -> Result<(Self, Vec<LenientWarning>), ParseError> {
+        lenient_specification(s)
+    }
+
+    // 没有marker时总是适用
+    pub fn evaluate<S: AsRef<str>>(&self, env: &MarkerEnvironment, extras: &[S]) -> bool {
+        self.marker_expr
+            .as_ref()
+            .is_none_or(|m| m.evaluate(env, extras))
+    }
+}
+
+// requirements file format, see https://pip.pypa.io/en/stable/reference/requirements-file-format/
+
+// requirements文件中pip支持的选项, 见pip中req_file.py的SUPPORTED_OPTIONS和SUPPORTED_OPTIONS_REQ
+// 短选项和长选项解析为同一个值, 输出时统一用长选项
+#[derive(Debug, PartialEq, Clone)]
+pub enum PipOption {
+    // -i, --index-url
+    IndexUrl(String),
+    ExtraIndexUrl(String),
+    NoIndex,
+    // -c, --constraint
+    Constraint(String),
+    // -r, --requirement
+    Requirement(String),
+    // -f, --find-links
+    FindLinks(String),
+    NoBinary(String),
+    OnlyBinary(String),
+    PreferBinary,
+    RequireHashes,
+    Pre,
+    TrustedHost(String),
+    UseFeature(String),
+    // 以下只能用在requirement行上
+    GlobalOption(String),
+    ConfigSettings(String),
+    Hash(HashAlgorithm, String),
+}
+
+// 选项表, 生成PIP_OPTIONS, PipOption::new和PipOption::name
+// 带值的选项给出构造函数, 值不合法时返回None
+macro_rules! pip_options {
+    ($($variant:ident: $short:expr, $long:literal $(, $value:ident => $ctor:expr)?;)*) => {
+        // (短选项, 长选项, 是否带值)
+        pub(crate) static PIP_OPTIONS: &[(Option<char>, &str, bool)] = &[
+            $(($short, $long, pip_options!(@takes_value $($value)?)),)*
+        ];
+
+        impl PipOption {
+            // name为长选项, 如"--index-url"; 值和name不匹配时返回None
+            pub fn new(name: &str, value: Option<&str>) -> Option<Self> {
+                match (name, value) {
+                    $(($long, pip_options!(@value $($value)?)) => pip_options!(@ctor $variant $(, $ctor)?),)*
+                    _ => None,
                 }
             }
-            if prospective.local.is_some() {
-                if prospective.to_base() == spec_v.to_base() {
-                    return false;
+
+            // 长选项的名字
+            pub fn name(&self) -> &'static str {
+                match self {
+                    $(Self::$variant { .. } => $long,)*
                 }
             }
-            true
-        } else {
-            false
+        }
+    };
+    (@takes_value) => { false };
+    (@takes_value $value:ident) => { true };
+    (@value) => { None };
+    (@value $value:ident) => { Some($value) };
+    (@ctor $variant:ident) => { Some(Self::$variant) };
+    (@ctor $variant:ident, $ctor:expr) => { $ctor };
+}
+
+pip_options! {
+    IndexUrl: Some('i'), "--index-url", v => Some(Self::IndexUrl(v.to_string()));
+    ExtraIndexUrl: None, "--extra-index-url", v => Some(Self::ExtraIndexUrl(v.to_string()));
+    NoIndex: None, "--no-index";
+    Constraint: Some('c'), "--constraint", v => Some(Self::Constraint(v.to_string()));
+    Requirement: Some('r'), "--requirement", v => Some(Self::Requirement(v.to_string()));
+    FindLinks: Some('f'), "--find-links", v => Some(Self::FindLinks(v.to_string()));
+    NoBinary: None, "--no-binary", v => Some(Self::NoBinary(v.to_string()));
+    OnlyBinary: None, "--only-binary", v => Some(Self::OnlyBinary(v.to_string()));
+    PreferBinary: None, "--prefer-binary";
+    RequireHashes: None, "--require-hashes";
+    Pre: None, "--pre";
+    TrustedHost: None, "--trusted-host", v => Some(Self::TrustedHost(v.to_string()));
+    UseFeature: None, "--use-feature", v => Some(Self::UseFeature(v.to_string()));
+    GlobalOption: None, "--global-option", v => Some(Self::GlobalOption(v.to_string()));
+    ConfigSettings: None, "--config-settings", v => Some(Self::ConfigSettings(v.to_string()));
+    Hash: None, "--hash", v => match hash_value(v) {
+        Ok(("", (algorithm, digest))) => Some(Self::Hash(algorithm, digest.to_string())),
+        _ => None,
+    };
+}
+
+impl PipOption {
+    // 选项的值, --hash为"<algorithm>:<digest>"
+    pub fn value(&self) -> Option<String> {
+        match self {
+            Self::IndexUrl(v)
+            | Self::ExtraIndexUrl(v)
+            | Self::Constraint(v)
+            | Self::Requirement(v)
+            | Self::FindLinks(v)
+            | Self::NoBinary(v)
+            | Self::OnlyBinary(v)
+            | Self::TrustedHost(v)
+            | Self::UseFeature(v)
+            | Self::GlobalOption(v)
+            | Self::ConfigSettings(v) => Some(v.clone()),
+            Self::Hash(algorithm, digest) => Some(format!("{}:{}", algorithm, digest)),
+            Self::NoIndex | Self::PreferBinary | Self::RequireHashes | Self::Pre => None,
         }
     }
-    fn compare_arbitrary(&self, prospective: &Version, spec: &str) -> bool {
-        prospective.to_string().eq_ignore_ascii_case(spec)
+
+    // 索引的url: --index-url, --extra-index-url和--find-links, 用于替换镜像地址
+    pub fn index_url_mut(&mut self) -> Option<&mut String> {
+        match self {
+            Self::IndexUrl(v) | Self::ExtraIndexUrl(v) | Self::FindLinks(v) => Some(v),
+            _ => None,
+        }
     }
 }
 
-#[derive(Debug, PartialEq, Default)]
-pub struct RequirementSpecifier {
-    pub name: String,
-    pub extras: Vec<String>,
-    pub version_specs: Vec<VersionSpec>,
-    pub urlspec: Option<String>,
+impl Display for PipOption {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.value() {
+            Some(value) => write!(f, "{} {}", self.name(), value),
+            None => write!(f, "{}", self.name()),
+        }
+    }
+}
+
+// 本地项目的位置
+#[derive(Debug, PartialEq, Clone)]
+pub enum ProjectLocation {
+    // 本地目录, 如 ./downloads/numpy
+    Path(String),
+    // file: url
+    Url(ParsedUrl),
+    // VCS url, 如 git+https://github.com/pypa/pip#egg=pip
+    Vcs(VcsUrl),
+}
+
+impl Display for ProjectLocation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Path(path) => write!(f, "{}", path),
+            Self::Url(url) => write!(f, "{}", url),
+            Self::Vcs(url) => write!(f, "{}", url),
+        }
+    }
+}
+
+// 本地项目或者可编辑安装的项目, 如 ./downloads/numpy[test], -e git+https://github.com/pypa/pip#egg=pip
+// 见pip中constructors.py的install_req_from_line和parse_editable
+#[derive(Debug, Clone)]
+pub struct ProjectRequirement {
+    pub location: ProjectLocation,
+    // 只有本地目录可以带[extras], url的extras在#egg=中
+    pub extras: Vec<ExtraName>,
+    pub editable: bool,
+    // pip忽略可编辑安装的marker, 所以只有非editable的项目才可能有
     pub marker_expr: Option<MarkerExpr>,
 }
 
-impl RequirementSpecifier {
-    pub fn contains_version(&self, version: &str) -> bool {
-        self.version_specs.iter().all(|spec| spec.contains(version))
+impl PartialEq for ProjectRequirement {
+    fn eq(&self, other: &Self) -> bool {
+        self.location == other.location
+            && extras_eq(&self.extras, &other.extras)
+            && self.editable == other.editable
+            && self.marker_expr == other.marker_expr
+    }
+}
+
+// url后面的';'前要有空白, 否则会被当作url的一部分
+impl Display for ProjectRequirement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.editable {
+            write!(f, "-e ")?;
+        }
+        write!(f, "{}", self.location)?;
+        fmt_extras(f, &self.extras)?;
+        if let Some(marker) = &self.marker_expr {
+            if !matches!(self.location, ProjectLocation::Path(_)) {
+                write!(f, " ")?;
+            }
+            write!(f, "; {}", marker)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Requirement {
+    Specifier(RequirementSpecifier),
+    // <archive url>, 如 https://example.com/foo-1.0-py3-none-any.whl
+    ArchiveUrl(ParsedUrl),
+    // 本地目录, file: url, 或者-e/--editable指定的项目
+    Project(ProjectRequirement),
+}
+
+impl Requirement {
+    pub fn marker_expr(&self) -> Option<&MarkerExpr> {
+        match self {
+            Self::Specifier(spec) => spec.marker_expr.as_ref(),
+            Self::ArchiveUrl(_) => None,
+            Self::Project(project) => project.marker_expr.as_ref(),
+        }
+    }
+
+    // 没有marker时总是适用
+    pub fn evaluate<S: AsRef<str>>(&self, env: &MarkerEnvironment, extras: &[S]) -> bool {
+        self.marker_expr().is_none_or(|m| m.evaluate(env, extras))
+    }
+}
+
+// 大部分行都是Requirement, 装箱没有好处
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, PartialEq)]
+pub enum LineContent {
+    // 空行或者只有注释的行
+    Empty,
+    // <requirement> [; markers] [[--option]...]
+    Requirement(Requirement, Vec<PipOption>),
+    // [[--option]...]
+    Options(Vec<PipOption>),
+}
+
+impl Display for Requirement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Specifier(spec) => write!(f, "{}", spec),
+            Self::ArchiveUrl(url) => write!(f, "{}", url),
+            Self::Project(project) => write!(f, "{}", project),
+        }
+    }
+}
+
+impl Display for LineContent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (requirement, options) = match self {
+            Self::Empty => return Ok(()),
+            Self::Requirement(r, o) => (Some(r), o),
+            Self::Options(o) => (None, o),
+        };
+        let mut sep = "";
+        if let Some(r) = requirement {
+            write!(f, "{}", r)?;
+            sep = " ";
+        }
+        for option in options {
+            write!(f, "{}{}", sep, option)?;
+            sep = " ";
+        }
+        Ok(())
+    }
+}
+
+// 一个逻辑行, 以'\'结尾的续行已经合并
+#[derive(Debug, PartialEq)]
+pub struct RequirementsFileLine {
+    // 逻辑行在文件中起始的行号, 从1开始
+    pub line_number: usize,
+    pub content: LineContent,
+    pub comment: Option<String>,
+    // 展开环境变量之前的内容(不含注释), 没有展开任何变量时为None
+    // content没有被修改时, 输出时用它代替content, 避免泄露变量的值
+    pub unexpanded: Option<Unexpanded>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Unexpanded {
+    pub text: String,
+    // 解析时content的规范化输出, 用于判断content是否被修改过
+    pub expanded: String,
+}
+
+impl RequirementsFileLine {
+    pub fn is_blank(&self) -> bool {
+        self.content == LineContent::Empty && self.comment.is_none()
+    }
+
+    pub fn is_comment(&self) -> bool {
+        self.content == LineContent::Empty && self.comment.is_some()
+    }
+}
+
+// 规范化的输出, 续行合并为一行; 行尾注释前要有空白, 否则'#'会被当作内容的一部分
+impl Display for RequirementsFileLine {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let content = self.content.to_string();
+        match &self.unexpanded {
+            // 修改之后的content只能输出展开之后的值
+            Some(unexpanded) if unexpanded.expanded == content => write!(f, "{}", unexpanded.text)?,
+            _ => write!(f, "{}", content)?,
+        }
+        match &self.comment {
+            Some(comment) if self.content == LineContent::Empty => write!(f, "#{}", comment),
+            Some(comment) => write!(f, " #{}", comment),
+            None => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Default)]
+pub struct RequirementsFile {
+    pub lines: Vec<RequirementsFileLine>,
+}
+
+impl RequirementsFile {
+    pub fn requirements(&self) -> impl Iterator<Item = &Requirement> {
+        self.lines.iter().filter_map(|l| match &l.content {
+            LineContent::Requirement(r, _) => Some(r),
+            _ => None,
+        })
+    }
+
+    pub fn options(&self) -> impl Iterator<Item = &PipOption> {
+        self.lines.iter().flat_map(|l| match &l.content {
+            LineContent::Options(o) => o.iter(),
+            _ => [].iter(),
+        })
+    }
+}
+
+// 每行都以'\n'结尾
+impl Display for RequirementsFile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for line in &self.lines {
+            writeln!(f, "{}", line)?;
+        }
+        Ok(())
+    }
+}
+
+impl RequirementsFile {
+    // 解析前展开${VAR}, 见EnvVars
+    pub fn parse_with_env(s: &str, env: &EnvVars) -> Result<Self, ParseError> {
+        requirements_file_with_env(s, env)
+    }
+}
+
+impl FromStr for RequirementsFile {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        requirements_file(s)
     }
 }
 
@@ -267,7 +1326,7 @@ impl Display for LocalVersionPart {
 // this is a version identifier, defined in pep 440, it is not the same as the string used in VersionSpec
 // public version identifier = [N!]N(.N)*[{a|b|rc}N][.postN][.devN]
 // local version identifier = <public version identifier>[+<local version label>]
-#[derive(Debug, Default, Eq)]
+#[derive(Debug, Clone, Default, Eq)]
 pub struct Version {
     pub epoch: u64,
     pub release: Vec<u64>,
@@ -302,20 +1361,32 @@ impl PartialEq for Version {
     }
 }
 
+impl FromStr for Version {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_complete(
+            s,
+            preceded(space0, expect("release number", version_scheme)),
+            "end of version",
+        )
+    }
+}
+
+type CmpKey<'a> = (
+    u64,
+    Vec<u64>,
+    (&'a str, u64),
+    (&'a str, u64),
+    (&'a str, u64),
+    &'a Vec<LocalVersionPart>,
+);
+
 static NEGATIVE_INFINITY_LOCAL: Vec<LocalVersionPart> = vec![];
-static INFINITY_PRE_POST_DEV: (&'static str, u64) = ("~", u64::MAX);
-static NEGATIVE_INFINITY_PRE_POST_DEV: (&'static str, u64) = ("!", 0);
+static INFINITY_PRE_POST_DEV: (&str, u64) = ("~", u64::MAX);
+static NEGATIVE_INFINITY_PRE_POST_DEV: (&str, u64) = ("!", 0);
 impl Version {
-    pub fn cmpkey(
-        &self,
-    ) -> (
-        u64,
-        Vec<u64>,
-        (&str, u64),
-        (&str, u64),
-        (&str, u64),
-        &Vec<LocalVersionPart>,
-    ) {
+    pub fn cmpkey(&self) -> CmpKey<'_> {
         let pre = if self.pre.is_none() && self.post.is_none() && self.dev.is_some() {
             NEGATIVE_INFINITY_PRE_POST_DEV
         } else {
@@ -389,7 +1460,7 @@ impl Version {
     // public_str = ver.public().to_string()
     pub fn to_public(&self) -> Self {
         Self {
-            epoch: self.epoch.clone(),
+            epoch: self.epoch,
             release: self.release.clone(),
             pre: self.pre.clone(),
             post: self.post.clone(),
@@ -400,7 +1471,7 @@ impl Version {
 
     pub fn to_base(&self) -> Self {
         Self {
-            epoch: self.epoch.clone(),
+            epoch: self.epoch,
             release: self.release.clone(),
             pre: None,
             post: None,
@@ -456,7 +1527,7 @@ impl Version {
         // local version segment
         if with_local {
             if let Some(local) = self.local.as_ref() {
-                parts.push_str("+");
+                parts.push('+');
                 for i in local.iter() {
                     parts.push_str(&format!("{}.", i));
                 }
@@ -0,0 +1,491 @@
+//! 无损的语法树(concrete syntax tree): 文件中的每个字节都属于某个token, Display原样还原文件
+//! 用于只改动一处(如升级一个版本)而保留格式, 注释, 续行和空行的编辑工具
+//! 每个逻辑行同时保留解析出的RequirementsFileLine; 编辑之后重新解析整个文件, 保证两者一致
+use std::{fmt::Display, ops::Range, str::FromStr};
+
+use nom::{
+    branch::alt,
+    character::complete::{space0, space1},
+    combinator::{eof, opt, peek, recognize},
+    sequence::{preceded, terminated, tuple},
+    Offset, Parser,
+};
+
+use super::{
+    LineContent, PackageName, Requirement, RequirementsFile, RequirementsFileLine, VersionSpec,
+};
+use crate::{
+    error::{IResult, ParseError},
+    parser::{
+        requirement_specifier::{extras, identifier, quoted_marker, urlspec, versionspec},
+        requirements_file::{
+            comment_start, logical_lines, pip_option, requirement, requirements_file, LogicalLine,
+        },
+    },
+};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum TokenKind {
+    // name[extras]versionspec @ url; marker 中的各部分
+    Name,
+    // 包括'['和']'
+    Extras,
+    // 包括括号(如果有)
+    VersionSpecs,
+    // 包括'@'
+    Url,
+    // 包括';'
+    Marker,
+    // 本地目录, archive url, -e/--editable指定的项目
+    Requirement,
+    // 一个选项和它的值, 如 --hash=sha256:...
+    Option,
+    // 包括'#'
+    Comment,
+    Whitespace,
+    // '\'和后面的换行符
+    Continuation,
+    Newline,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Token {
+    pub kind: TokenKind,
+    // 在所在逻辑行的text中的字节范围
+    pub range: Range<usize>,
+}
+
+// 一个逻辑行的原文, 包括续行和行尾的换行符
+#[derive(Debug, PartialEq)]
+pub struct CstLine {
+    pub text: String,
+    // 按顺序排列, 首尾相接覆盖整个text
+    pub tokens: Vec<Token>,
+    pub line: RequirementsFileLine,
+}
+
+impl CstLine {
+    pub fn tokens(&self) -> impl Iterator<Item = (TokenKind, &str)> {
+        self.tokens
+            .iter()
+            .map(|t| (t.kind, &self.text[t.range.clone()]))
+    }
+
+    fn token(&self, kind: TokenKind) -> Option<&Token> {
+        self.tokens.iter().find(|t| t.kind == kind)
+    }
+
+    fn is_requirement_named(&self, name: &PackageName) -> bool {
+        matches!(
+            &self.line.content,
+            LineContent::Requirement(Requirement::Specifier(spec), _) if spec.name == *name
+        )
+    }
+
+    // 替换或者插入版本约束之后的text; 有url的requirement不能指定版本, 返回None
+    fn with_version_specs(&self, version_specs: &[VersionSpec]) -> Option<String> {
+        if self.token(TokenKind::Url).is_some() {
+            return None;
+        }
+        let specs: Vec<String> = version_specs.iter().map(|s| s.to_string()).collect();
+        let range = match self.token(TokenKind::VersionSpecs) {
+            Some(token) => token.range.clone(),
+            // 插入到name[extras]之后
+            None => {
+                let end = self
+                    .tokens
+                    .iter()
+                    .filter(|t| matches!(t.kind, TokenKind::Name | TokenKind::Extras))
+                    .map(|t| t.range.end)
+                    .max()?;
+                end..end
+            }
+        };
+        let mut text = self.text.clone();
+        text.replace_range(range, &specs.join(","));
+        Some(text)
+    }
+}
+
+impl Display for CstLine {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.text)
+    }
+}
+
+#[derive(Debug, PartialEq, Default)]
+pub struct RequirementsFileCst {
+    pub lines: Vec<CstLine>,
+}
+
+// 和name_req/url_req的语法相同, 返回各部分的原文
+fn specifier_parts(input: &str) -> IResult<&str, Vec<(TokenKind, &str)>> {
+    tuple((
+        recognize(identifier),
+        preceded(space0, opt(recognize(extras))),
+        preceded(
+            space0,
+            opt(alt((
+                terminated(recognize(urlspec), peek(alt((space1, eof))))
+                    .map(|u| (TokenKind::Url, u)),
+                recognize(versionspec).map(|v| (TokenKind::VersionSpecs, v)),
+            ))),
+        ),
+        preceded(space0, opt(recognize(quoted_marker))),
+    ))
+    .map(|(name, extras, spec, marker)| {
+        let mut parts = vec![(TokenKind::Name, name)];
+        parts.extend(extras.map(|e| (TokenKind::Extras, e)));
+        parts.extend(spec);
+        parts.extend(marker.map(|m| (TokenKind::Marker, m)));
+        parts
+    })
+    .parse(input)
+}
+
+// 去掉注释之后的内容中的token, content已经成功解析为line
+fn content_tokens<'a>(content: &'a str, line: &LineContent) -> Vec<(TokenKind, &'a str)> {
+    let mut tokens = vec![];
+    let mut rest = content.trim_start();
+    if let LineContent::Requirement(r, _) = line {
+        let Ok((after, consumed)) = recognize(requirement)(rest) else {
+            return tokens;
+        };
+        match (r, specifier_parts(consumed)) {
+            (Requirement::Specifier(_), Ok((_, parts))) => tokens.extend(parts),
+            _ => tokens.push((TokenKind::Requirement, consumed)),
+        }
+        rest = after;
+    }
+    while let Ok((after, option)) = preceded(space0, recognize(pip_option))(rest) {
+        tokens.push((TokenKind::Option, option));
+        rest = after;
+    }
+    tokens
+}
+
+// 语法token之间的部分: 空白, 续行符和换行符
+fn trivia_tokens(text: &str, start: usize) -> Vec<Token> {
+    let mut tokens = vec![];
+    let mut i = 0;
+    while i < text.len() {
+        let rest = &text[i..];
+        let (kind, len) = match ["\\\r\n", "\\\n", "\r\n", "\n"]
+            .iter()
+            .find(|p| rest.starts_with(**p))
+        {
+            Some(p) if p.starts_with('\\') => (TokenKind::Continuation, p.len()),
+            Some(p) => (TokenKind::Newline, p.len()),
+            // 文件末尾的'\'
+            None if rest == "\\" => (TokenKind::Continuation, 1),
+            None => {
+                let len = rest.find(['\\', '\r', '\n']).unwrap_or(rest.len());
+                (TokenKind::Whitespace, len.max(1))
+            }
+        };
+        tokens.push(Token {
+            kind,
+            range: start + i..start + i + len,
+        });
+        i += len;
+    }
+    tokens
+}
+
+// logical的偏移是相对于整个文件的, start是这个逻辑行在文件中的起点
+fn line_tokens(
+    text: &str,
+    start: usize,
+    logical: &LogicalLine,
+    line: &RequirementsFileLine,
+) -> Vec<Token> {
+    let comment = comment_start(&logical.text);
+    let content = &logical.text[..comment.unwrap_or(logical.text.len())];
+    let mut syntax = content_tokens(content, &line.content);
+    if let Some(i) = comment {
+        syntax.push((TokenKind::Comment, &logical.text[i..]));
+    }
+    let mut tokens = vec![];
+    let mut end = 0;
+    for (kind, part) in syntax {
+        let part = part.trim_end();
+        if part.is_empty() {
+            continue;
+        }
+        // 跨过续行的token在原文中包括中间的'\'和换行符
+        let offset = logical.text.offset(part);
+        let range = logical.source_offset(offset) - start
+            ..logical.source_offset(offset + part.len() - 1) + 1 - start;
+        tokens.extend(trivia_tokens(&text[end..range.start], end));
+        end = range.end;
+        tokens.push(Token { kind, range });
+    }
+    tokens.extend(trivia_tokens(&text[end..], end));
+    tokens
+}
+
+impl RequirementsFileCst {
+    pub fn parse(input: &str) -> Result<Self, ParseError> {
+        let file = requirements_file(input)?;
+        let logical = logical_lines(input);
+        let starts: Vec<usize> = logical.iter().map(|l| l.segments[0].1).collect();
+        let mut lines = vec![];
+        for (i, (logical, line)) in logical.iter().zip(file.lines).enumerate() {
+            let start = starts[i];
+            let end = starts.get(i + 1).copied().unwrap_or(input.len());
+            let text = &input[start..end];
+            lines.push(CstLine {
+                text: text.to_string(),
+                tokens: line_tokens(text, start, logical, &line),
+                line,
+            });
+        }
+        Ok(Self { lines })
+    }
+
+    // 在文件中的偏移
+    pub fn tokens(&self) -> impl Iterator<Item = (TokenKind, Range<usize>)> + '_ {
+        self.lines
+            .iter()
+            .scan(0, |start, line| {
+                let line_start = *start;
+                *start += line.text.len();
+                Some((line_start, line))
+            })
+            .flat_map(|(start, line)| {
+                line.tokens
+                    .iter()
+                    .map(move |t| (t.kind, start + t.range.start..start + t.range.end))
+            })
+    }
+
+    pub fn token_at(&self, offset: usize) -> Option<(TokenKind, Range<usize>)> {
+        self.tokens().find(|(_, range)| range.contains(&offset))
+    }
+
+    // 第一个名字为name的requirement所在的行
+    pub fn position(&self, name: &PackageName) -> Option<usize> {
+        self.lines.iter().position(|l| l.is_requirement_named(name))
+    }
+
+    // 把名字为name的requirement的版本约束替换为version_specs, 为空时去掉版本约束
+    // 只改动版本约束的原文; 返回修改的行数
+    pub fn set_version(
+        &mut self,
+        name: &PackageName,
+        version_specs: &[VersionSpec],
+    ) -> Result<usize, ParseError> {
+        let mut changed = 0;
+        let mut text = String::new();
+        for line in &self.lines {
+            let new_text = line
+                .is_requirement_named(name)
+                .then(|| line.with_version_specs(version_specs))
+                .flatten();
+            match new_text {
+                Some(new_text) => {
+                    text.push_str(&new_text);
+                    changed += 1;
+                }
+                None => text.push_str(&line.text),
+            }
+        }
+        *self = Self::parse(&text)?;
+        Ok(changed)
+    }
+
+    // 去掉整个逻辑行, 包括它的续行和注释
+    pub fn remove_line(&mut self, index: usize) -> CstLine {
+        let removed = self.lines.remove(index);
+        let removed_lines = removed.text.matches('\n').count();
+        for line in &mut self.lines[index..] {
+            line.line.line_number -= removed_lines;
+        }
+        removed
+    }
+
+    // 在文件末尾添加一行
+    pub fn add_requirement(&mut self, requirement: &Requirement) -> Result<(), ParseError> {
+        let mut text = self.to_string();
+        // 末尾的'\'会和下一行合并, 用一个空行结束它
+        if text.ends_with('\\') {
+            text.push_str("\n\n");
+        } else if !text.is_empty() && !text.ends_with('\n') {
+            text.push('\n');
+        }
+        text.push_str(&format!("{}\n", requirement));
+        *self = Self::parse(&text)?;
+        Ok(())
+    }
+
+    pub fn into_requirements_file(self) -> RequirementsFile {
+        RequirementsFile {
+            lines: self.lines.into_iter().map(|l| l.line).collect(),
+        }
+    }
+}
+
+impl Display for RequirementsFileCst {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for line in &self.lines {
+            write!(f, "{}", line)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for RequirementsFileCst {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RequirementsFileCst, TokenKind};
+    use crate::requirements::{PackageName, Requirement};
+
+    static CONTENT: &str = "# pinned by hand\r
+--index-url https://pypi.org/simple\r
+\r
+requests [security] >= 2.8.1, == 2.8.* ; python_version < \"2.7\"  # trailing\r
+Django \\\r
+    >=3.2,<4 \\\r
+    --hash=sha256:abcdef
+-e ./vendor/foo  # local
+flask==2.0 \\
+# comment after continuation
+pkg @ https://example.com/pkg-1.0.tar.gz ; os_name == \"nt\"
+six
+attrs\\";
+
+    fn cst(s: &str) -> RequirementsFileCst {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_lossless() {
+        let file = cst(CONTENT);
+        assert_eq!(file.to_string(), CONTENT);
+        // token首尾相接覆盖整个文件
+        let mut end = 0;
+        for (_, range) in file.tokens() {
+            assert_eq!(range.start, end);
+            assert!(range.end > range.start);
+            end = range.end;
+        }
+        assert_eq!(end, CONTENT.len());
+        assert_eq!(
+            file.lines
+                .iter()
+                .map(|l| l.line.line_number)
+                .collect::<Vec<_>>(),
+            [1, 2, 3, 4, 5, 8, 9, 11, 12, 13]
+        );
+    }
+
+    #[test]
+    fn test_tokens() {
+        let file = cst(CONTENT);
+        let syntax = |i: usize| -> Vec<(TokenKind, &str)> {
+            file.lines[i]
+                .tokens()
+                .filter(|(kind, _)| !matches!(kind, TokenKind::Whitespace | TokenKind::Newline))
+                .collect()
+        };
+        assert_eq!(
+            syntax(3),
+            [
+                (TokenKind::Name, "requests"),
+                (TokenKind::Extras, "[security]"),
+                (TokenKind::VersionSpecs, ">= 2.8.1, == 2.8.*"),
+                (TokenKind::Marker, "; python_version < \"2.7\""),
+                (TokenKind::Comment, "# trailing"),
+            ]
+        );
+        assert_eq!(
+            syntax(4),
+            [
+                (TokenKind::Name, "Django"),
+                (TokenKind::Continuation, "\\\r\n"),
+                (TokenKind::VersionSpecs, ">=3.2,<4"),
+                (TokenKind::Continuation, "\\\r\n"),
+                (TokenKind::Option, "--hash=sha256:abcdef"),
+            ]
+        );
+        assert_eq!(
+            syntax(5),
+            [
+                (TokenKind::Requirement, "-e ./vendor/foo"),
+                (TokenKind::Comment, "# local")
+            ]
+        );
+        assert_eq!(
+            syntax(6)[3],
+            (TokenKind::Comment, "# comment after continuation")
+        );
+        assert_eq!(
+            syntax(7)[1],
+            (TokenKind::Url, "@ https://example.com/pkg-1.0.tar.gz")
+        );
+        assert_eq!(
+            syntax(9),
+            [(TokenKind::Name, "attrs"), (TokenKind::Continuation, "\\")]
+        );
+        let offset = CONTENT.find("<4").unwrap();
+        assert_eq!(
+            file.token_at(offset),
+            Some((TokenKind::VersionSpecs, offset - 6..offset + 2))
+        );
+    }
+
+    #[test]
+    fn test_set_version() {
+        let mut file = cst(CONTENT);
+        let specs = [">=4.2".parse().unwrap(), "<5".parse().unwrap()];
+        assert_eq!(file.set_version(&PackageName::new("django"), &specs), Ok(1));
+        assert_eq!(file.to_string(), CONTENT.replace(">=3.2,<4", ">=4.2,<5"),);
+
+        let mut file = cst(CONTENT);
+        let specs = ["==2.31.0".parse().unwrap()];
+        assert_eq!(
+            file.set_version(&PackageName::new("Requests"), &specs),
+            Ok(1)
+        );
+        assert_eq!(
+            file.to_string(),
+            CONTENT.replace(">= 2.8.1, == 2.8.*", "==2.31.0"),
+        );
+        // 插入版本约束, 有url时不修改
+        assert_eq!(file.set_version(&PackageName::new("attrs"), &specs), Ok(1));
+        assert!(file.to_string().ends_with("attrs==2.31.0\\"));
+        assert_eq!(file.set_version(&PackageName::new("pkg"), &specs), Ok(0));
+        assert_eq!(
+            file.set_version(&PackageName::new("missing"), &specs),
+            Ok(0)
+        );
+    }
+
+    #[test]
+    fn test_remove_and_add() {
+        let mut file = cst(CONTENT);
+        let index = file.position(&PackageName::new("django")).unwrap();
+        let removed = file.remove_line(index);
+        assert_eq!(
+            removed.text,
+            "Django \\\r\n    >=3.2,<4 \\\r\n    --hash=sha256:abcdef\n"
+        );
+        assert_eq!(file.to_string(), CONTENT.replace(&removed.text, ""));
+        assert_eq!(file.lines[index].line.line_number, 5);
+
+        let requirement: Requirement = Requirement::Specifier("numpy>=1.26".parse().unwrap());
+        file.add_requirement(&requirement).unwrap();
+        assert!(file.to_string().ends_with("attrs\\\n\nnumpy>=1.26\n"));
+        let mut file = cst("");
+        file.add_requirement(&requirement).unwrap();
+        assert_eq!(file.to_string(), "numpy>=1.26\n");
+        assert_eq!(file.into_requirements_file().requirements().count(), 1);
+    }
+}
@@ -0,0 +1,457 @@
+//! hash-checking mode, 见<https://pip.pypa.io/en/stable/topics/secure-installs/>
+//! requirement行上的--hash选项解析为Hashes; 启用verify feature时可以校验下载的文件
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Display,
+    str::FromStr,
+};
+
+use super::{
+    sdist::SdistFormat, url::ParsedUrl, Comparison, LineContent, PipOption, ProjectLocation,
+    Requirement, RequirementsFile, RequirementsFileLine, VersionPattern,
+};
+use crate::error::ParseError;
+
+// pip中utils/hashes.py的STRONG_HASHES, --hash只允许这几种
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
+pub enum HashAlgorithm {
+    Sha256,
+    Sha384,
+    Sha512,
+}
+
+impl HashAlgorithm {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Sha256 => "sha256",
+            Self::Sha384 => "sha384",
+            Self::Sha512 => "sha512",
+        }
+    }
+}
+
+impl Display for HashAlgorithm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for HashAlgorithm {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "sha256" => Ok(Self::Sha256),
+            "sha384" => Ok(Self::Sha384),
+            "sha512" => Ok(Self::Sha512),
+            _ => Err(ParseError::new(s, 0, "sha256, sha384 or sha512")),
+        }
+    }
+}
+
+// 允许的摘要, 和pip中utils/hashes.py的Hashes一样: 任意一个算法的任意一个摘要匹配即可
+// 摘要统一为小写的十六进制
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct Hashes(BTreeMap<HashAlgorithm, BTreeSet<String>>);
+
+impl Hashes {
+    pub fn insert(&mut self, algorithm: HashAlgorithm, hex_digest: &str) {
+        self.0
+            .entry(algorithm)
+            .or_default()
+            .insert(hex_digest.to_ascii_lowercase());
+    }
+
+    // 按pip的规则从一行的选项中收集--hash
+    pub fn from_options<'a>(options: impl IntoIterator<Item = &'a PipOption>) -> Self {
+        let mut hashes = Self::default();
+        for option in options {
+            if let PipOption::Hash(algorithm, digest) = option {
+                hashes.insert(*algorithm, digest);
+            }
+        }
+        hashes
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn algorithms(&self) -> impl Iterator<Item = HashAlgorithm> + '_ {
+        self.0.keys().copied()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (HashAlgorithm, &str)> {
+        self.0
+            .iter()
+            .flat_map(|(a, digests)| digests.iter().map(move |d| (*a, d.as_str())))
+    }
+
+    pub fn is_allowed(&self, algorithm: HashAlgorithm, hex_digest: &str) -> bool {
+        self.0
+            .get(&algorithm)
+            .is_some_and(|digests| digests.contains(&hex_digest.to_ascii_lowercase()))
+    }
+}
+
+impl RequirementsFileLine {
+    // requirement行上的--hash, 加上archive url中#sha256=...形式的hash
+    pub fn hashes(&self) -> Hashes {
+        match &self.content {
+            LineContent::Requirement(requirement, options) => {
+                let mut hashes = Hashes::from_options(options);
+                if let Requirement::ArchiveUrl(url) = requirement {
+                    if let Some((algorithm, digest)) = url_hash(url) {
+                        hashes.insert(algorithm, digest);
+                    }
+                }
+                hashes
+            }
+            _ => Hashes::default(),
+        }
+    }
+}
+
+fn url_hash(url: &ParsedUrl) -> Option<(HashAlgorithm, &str)> {
+    let (name, digest) = url.hash()?;
+    Some((name.parse().ok()?, digest))
+}
+
+// require hashes模式下requirement不满足要求的原因
+// 见pip中resolution/resolvelib/factory.py和operations/prepare.py中的hash检查
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum HashCheckError {
+    // 没有--hash
+    MissingHash,
+    // 版本没有用==或者===固定, 如 foo>=1.0 或者 foo==1.*
+    NotPinned,
+    // 可编辑安装, 本地目录和VCS url没有办法计算hash
+    Unhashable,
+}
+
+impl Display for HashCheckError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MissingHash => write!(f, "missing --hash"),
+            Self::NotPinned => write!(f, "not pinned with =="),
+            Self::Unhashable => write!(f, "cannot be hashed"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct HashCheckReport<'a> {
+    pub line_number: usize,
+    pub requirement: &'a Requirement,
+    pub errors: Vec<HashCheckError>,
+}
+
+// 本地路径或者url是否指向一个归档文件, 而不是目录
+fn is_archive(filename: &str) -> bool {
+    filename.to_ascii_lowercase().ends_with(".whl")
+        || SdistFormat::split_extension(filename).is_some()
+}
+
+// 见packaging中SpecifierSet的用法和pip中req_install.py的is_pinned
+fn is_pinned(requirement: &Requirement) -> bool {
+    match requirement {
+        Requirement::Specifier(spec) if spec.urlspec.is_some() => true,
+        Requirement::Specifier(spec) => match spec.version_specs.as_slice() {
+            [spec] => match (&spec.0, &spec.1) {
+                (Comparison::Equal, VersionPattern::Version { wildcard, .. }) => !wildcard,
+                (Comparison::ArbitraryEqual, _) => true,
+                _ => false,
+            },
+            _ => false,
+        },
+        _ => true,
+    }
+}
+
+fn is_hashable(requirement: &Requirement) -> bool {
+    match requirement {
+        Requirement::Project(project) if project.editable => false,
+        Requirement::Project(project) => match &project.location {
+            ProjectLocation::Path(path) => is_archive(path),
+            ProjectLocation::Url(url) => url.filename().is_some_and(is_archive),
+            ProjectLocation::Vcs(_) => false,
+        },
+        _ => true,
+    }
+}
+
+impl RequirementsFile {
+    // 有--require-hashes选项, 或者任意一个requirement带--hash时, pip进入hash-checking mode
+    // 和pip中req_install.py的has_hash_options一样只看--hash, url中的#sha256=...不算
+    pub fn requires_hashes(&self) -> bool {
+        self.options()
+            .any(|o| matches!(o, PipOption::RequireHashes))
+            || self.lines.iter().any(|l| match &l.content {
+                LineContent::Requirement(_, options) => !Hashes::from_options(options).is_empty(),
+                _ => false,
+            })
+    }
+
+    // 按hash-checking mode检查每个requirement, 只返回有问题的行
+    pub fn check_hashes(&self) -> Vec<HashCheckReport<'_>> {
+        self.lines
+            .iter()
+            .filter_map(|line| {
+                let LineContent::Requirement(requirement, _) = &line.content else {
+                    return None;
+                };
+                let mut errors = vec![];
+                if !is_hashable(requirement) {
+                    errors.push(HashCheckError::Unhashable);
+                } else if line.hashes().is_empty() {
+                    errors.push(HashCheckError::MissingHash);
+                }
+                if !is_pinned(requirement) {
+                    errors.push(HashCheckError::NotPinned);
+                }
+                (!errors.is_empty()).then_some(HashCheckReport {
+                    line_number: line.line_number,
+                    requirement,
+                    errors,
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(feature = "verify")]
+pub use verify::HashError;
+
+// 流式计算文件的摘要, 见pip中utils/hashes.py的check_against_chunks
+#[cfg(feature = "verify")]
+mod verify {
+    use std::{
+        collections::BTreeMap,
+        fmt::Display,
+        fs::File,
+        io::{self, Read},
+        path::Path,
+    };
+
+    use sha2::{Digest, Sha256, Sha384, Sha512};
+
+    use super::{HashAlgorithm, Hashes};
+
+    #[derive(Debug)]
+    pub enum HashError {
+        Io(io::Error),
+        // 没有允许的摘要, 对应pip的HashMissing
+        NoHashes,
+        // 对应pip的HashMismatch, got是每个算法实际的摘要
+        Mismatch {
+            got: BTreeMap<HashAlgorithm, String>,
+        },
+    }
+
+    impl Display for HashError {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            match self {
+                Self::Io(e) => write!(f, "{}", e),
+                Self::NoHashes => write!(f, "no hashes to check against"),
+                Self::Mismatch { got } => {
+                    write!(f, "hashes do not match")?;
+                    for (algorithm, digest) in got {
+                        write!(f, ", got {}:{}", algorithm, digest)?;
+                    }
+                    Ok(())
+                }
+            }
+        }
+    }
+
+    impl std::error::Error for HashError {}
+
+    impl From<io::Error> for HashError {
+        fn from(e: io::Error) -> Self {
+            Self::Io(e)
+        }
+    }
+
+    enum Hasher {
+        Sha256(Sha256),
+        Sha384(Sha384),
+        Sha512(Sha512),
+    }
+
+    impl Hasher {
+        fn new(algorithm: HashAlgorithm) -> Self {
+            match algorithm {
+                HashAlgorithm::Sha256 => Self::Sha256(Sha256::new()),
+                HashAlgorithm::Sha384 => Self::Sha384(Sha384::new()),
+                HashAlgorithm::Sha512 => Self::Sha512(Sha512::new()),
+            }
+        }
+
+        fn update(&mut self, chunk: &[u8]) {
+            match self {
+                Self::Sha256(h) => h.update(chunk),
+                Self::Sha384(h) => h.update(chunk),
+                Self::Sha512(h) => h.update(chunk),
+            }
+        }
+
+        fn hex_digest(self) -> String {
+            let digest = match self {
+                Self::Sha256(h) => h.finalize().to_vec(),
+                Self::Sha384(h) => h.finalize().to_vec(),
+                Self::Sha512(h) => h.finalize().to_vec(),
+            };
+            digest.iter().map(|b| format!("{:02x}", b)).collect()
+        }
+    }
+
+    impl Hashes {
+        // 只计算出现过的算法
+        pub fn check_reader<R: Read>(&self, mut reader: R) -> Result<(), HashError> {
+            if self.is_empty() {
+                return Err(HashError::NoHashes);
+            }
+            let mut hashers: Vec<(HashAlgorithm, Hasher)> = self
+                .algorithms()
+                .map(|algorithm| (algorithm, Hasher::new(algorithm)))
+                .collect();
+            let mut buf = [0; 8192];
+            loop {
+                let n = match reader.read(&mut buf) {
+                    Ok(0) => break,
+                    Ok(n) => n,
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                    Err(e) => return Err(e.into()),
+                };
+                for (_, hasher) in &mut hashers {
+                    hasher.update(&buf[..n]);
+                }
+            }
+            let got: BTreeMap<HashAlgorithm, String> = hashers
+                .into_iter()
+                .map(|(algorithm, hasher)| (algorithm, hasher.hex_digest()))
+                .collect();
+            if got.iter().any(|(a, d)| self.is_allowed(*a, d)) {
+                Ok(())
+            } else {
+                Err(HashError::Mismatch { got })
+            }
+        }
+
+        pub fn check_file<P: AsRef<Path>>(&self, path: P) -> Result<(), HashError> {
+            self.check_reader(File::open(path)?)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{HashAlgorithm, HashCheckError, Hashes};
+    use crate::requirements::RequirementsFile;
+
+    // sha256("hello")
+    const HELLO_SHA256: &str = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+
+    #[test]
+    fn test_parse_hashes() {
+        let file: RequirementsFile = format!(
+            "foo==1.0 \\
+    --hash=sha256:{} \\
+    --hash sha512:ABCDEF
+bar>=2 --hash=sha384:0123
+./local/dir
+-e ./editable
+https://example.com/baz-1.0.tar.gz#sha256=abcd
+qux===1.0
+",
+            HELLO_SHA256
+        )
+        .parse()
+        .unwrap();
+        let hashes = file.lines[0].hashes();
+        assert!(hashes.is_allowed(HashAlgorithm::Sha256, &HELLO_SHA256.to_uppercase()));
+        assert!(hashes.is_allowed(HashAlgorithm::Sha512, "abcdef"));
+        assert!(!hashes.is_allowed(HashAlgorithm::Sha384, "abcdef"));
+        assert_eq!(hashes.iter().count(), 2);
+        assert!(file.lines[4]
+            .hashes()
+            .is_allowed(HashAlgorithm::Sha256, "abcd"));
+        assert!(file.requires_hashes());
+
+        let report: Vec<_> = file
+            .check_hashes()
+            .into_iter()
+            .map(|r| (r.line_number, r.errors))
+            .collect();
+        assert_eq!(
+            report,
+            vec![
+                (4, vec![HashCheckError::NotPinned]),
+                (5, vec![HashCheckError::Unhashable]),
+                (6, vec![HashCheckError::Unhashable]),
+                (8, vec![HashCheckError::MissingHash]),
+            ]
+        );
+
+        assert!(!"foo==1.0\n"
+            .parse::<RequirementsFile>()
+            .unwrap()
+            .requires_hashes());
+        assert!("--require-hashes\nfoo==1.0\n"
+            .parse::<RequirementsFile>()
+            .unwrap()
+            .requires_hashes());
+        // url中的#sha256=...不会开启hash-checking mode
+        let file: RequirementsFile = "https://example.com/foo-1.0.tar.gz#sha256=abcd\nbar>=1\n"
+            .parse()
+            .unwrap();
+        assert!(!file.requires_hashes());
+        assert!(file.lines[0]
+            .hashes()
+            .is_allowed(HashAlgorithm::Sha256, "abcd"));
+        for line in [
+            "foo --hash=md5:abcd",
+            "foo --hash=sha256",
+            "foo --hash=sha256:xyz",
+        ] {
+            let e = line.parse::<RequirementsFile>().unwrap_err();
+            assert_eq!(
+                (e.column, e.expected.as_str()),
+                (12, "sha256, sha384 or sha512 hash")
+            );
+        }
+        let e = "md5".parse::<HashAlgorithm>().unwrap_err();
+        assert_eq!(
+            (e.column, e.expected.as_str()),
+            (1, "sha256, sha384 or sha512")
+        );
+        assert_eq!(Hashes::default(), Hashes::from_options(&[]));
+    }
+
+    #[cfg(feature = "verify")]
+    #[test]
+    fn test_check_reader() {
+        use super::HashError;
+
+        let mut hashes = Hashes::default();
+        assert!(matches!(
+            hashes.check_reader(&b"hello"[..]),
+            Err(HashError::NoHashes)
+        ));
+        hashes.insert(HashAlgorithm::Sha512, "00");
+        hashes.insert(HashAlgorithm::Sha256, HELLO_SHA256);
+        assert!(hashes.check_reader(&b"hello"[..]).is_ok());
+        match hashes.check_reader(&b"hello!"[..]) {
+            Err(HashError::Mismatch { got }) => {
+                assert_eq!(got.len(), 2);
+                assert_eq!(got[&HashAlgorithm::Sha256].len(), 64);
+                assert_eq!(got[&HashAlgorithm::Sha512].len(), 128);
+            }
+            r => panic!("{:?}", r),
+        }
+        assert!(matches!(
+            hashes.check_file("/nonexistent/foo-1.0.tar.gz"),
+            Err(HashError::Io(_))
+        ));
+    }
+}
@@ -0,0 +1,404 @@
+//! 递归加载-r/-c引用的requirements文件, 见pip中req_file.py的parse_requirements和handle_option_line
+//! 被引用的路径相对于引用它的文件; 读文件通过FileReader, 测试时可以用内存中的HashMap
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    fs, io,
+    path::{Component, Path, PathBuf},
+};
+
+use super::{url::ParsedUrl, LineContent, PipOption, Requirement, RequirementsFile};
+use crate::{
+    error::ParseError,
+    parser::requirements_file::{url_scheme, EnvVars},
+};
+
+pub trait FileReader {
+    fn read(&self, path: &Path) -> io::Result<String>;
+}
+
+// 从文件系统读取
+#[derive(Debug, Default, Clone, Copy)]
+pub struct FsReader;
+
+impl FileReader for FsReader {
+    fn read(&self, path: &Path) -> io::Result<String> {
+        fs::read_to_string(path)
+    }
+}
+
+// 路径 -> 内容, 路径要和normalize_path之后的一致
+impl FileReader for HashMap<PathBuf, String> {
+    fn read(&self, path: &Path) -> io::Result<String> {
+        self.get(path)
+            .cloned()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "No such file"))
+    }
+}
+
+// 出处: 文件和逻辑行的起始行号
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Source {
+    pub path: PathBuf,
+    pub line_number: usize,
+}
+
+impl Display for Source {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.path.display(), self.line_number)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct LoadedRequirement {
+    pub requirement: Requirement,
+    // 同一行上的选项, 如--hash
+    pub options: Vec<PipOption>,
+    pub source: Source,
+    // 来自-c引用的文件, 只约束版本, 不会被安装
+    pub constraint: bool,
+}
+
+// 合并之后的结果, 按pip处理的顺序排列
+#[derive(Debug, PartialEq, Default)]
+pub struct LoadedRequirements {
+    pub requirements: Vec<LoadedRequirement>,
+    // -r/-c以外的选项, 如--index-url
+    pub options: Vec<(PipOption, Source)>,
+    // 按加载的顺序, 同一个文件被引用多次时会出现多次
+    pub files: Vec<PathBuf>,
+}
+
+#[derive(Debug)]
+pub enum LoadError {
+    // included_from为None时是最外层的文件
+    Io {
+        path: PathBuf,
+        included_from: Option<Source>,
+        error: io::Error,
+    },
+    Parse {
+        path: PathBuf,
+        error: ParseError,
+    },
+    // 从最外层到重复出现的文件, 最后一项和前面的某一项相同
+    Cycle {
+        chain: Vec<PathBuf>,
+        source: Source,
+    },
+    // 只支持本地文件和file: url
+    UnsupportedUrl {
+        url: String,
+        source: Source,
+    },
+}
+
+impl Display for LoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io {
+                path,
+                included_from: Some(source),
+                error,
+            } => write!(f, "{}: {}: {}", source, path.display(), error),
+            Self::Io { path, error, .. } => write!(f, "{}: {}", path.display(), error),
+            Self::Parse { path, error } => write!(f, "{}:{}", path.display(), error),
+            Self::Cycle { chain, source } => {
+                let chain: Vec<_> = chain.iter().map(|p| p.display().to_string()).collect();
+                write!(f, "{}: include cycle: {}", source, chain.join(" -> "))
+            }
+            Self::UnsupportedUrl { url, source } => {
+                write!(f, "{}: unsupported include url {}", source, url)
+            }
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+// 只按字面去掉'.'和'..', 不访问文件系统
+pub fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => match normalized.components().next_back() {
+                Some(Component::Normal(_)) => {
+                    normalized.pop();
+                }
+                Some(Component::RootDir | Component::Prefix(_)) => {}
+                _ => normalized.push(".."),
+            },
+            _ => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+// -r/-c的值, 相对路径相对于引用它的文件所在的目录
+fn include_path(including: &Path, value: &str, source: &Source) -> Result<PathBuf, LoadError> {
+    let path = match url_scheme(value) {
+        Some(scheme) if scheme.eq_ignore_ascii_case("file") => value
+            .parse::<ParsedUrl>()
+            .map(|url| PathBuf::from(url.path))
+            .map_err(|_| LoadError::UnsupportedUrl {
+                url: value.to_string(),
+                source: source.clone(),
+            })?,
+        Some(_) => {
+            return Err(LoadError::UnsupportedUrl {
+                url: value.to_string(),
+                source: source.clone(),
+            })
+        }
+        None => including.parent().unwrap_or(Path::new("")).join(value),
+    };
+    Ok(normalize_path(&path))
+}
+
+struct Loader<'r, R: FileReader> {
+    reader: &'r R,
+    env: &'r EnvVars,
+    // 正在加载的文件, 用来检测循环引用
+    stack: Vec<PathBuf>,
+    loaded: LoadedRequirements,
+}
+
+impl<R: FileReader> Loader<'_, R> {
+    fn load(
+        &mut self,
+        path: PathBuf,
+        constraint: bool,
+        included_from: Option<Source>,
+    ) -> Result<(), LoadError> {
+        if self.stack.contains(&path) {
+            let mut chain = self.stack.clone();
+            chain.push(path);
+            return Err(LoadError::Cycle {
+                chain,
+                // 只有最外层的文件没有included_from, 它不可能出现在循环的末尾
+                source: included_from.expect("cycle in the top-level file"),
+            });
+        }
+        let content = self.reader.read(&path).map_err(|error| LoadError::Io {
+            path: path.clone(),
+            included_from,
+            error,
+        })?;
+        let file = RequirementsFile::parse_with_env(&content, self.env).map_err(|error| {
+            LoadError::Parse {
+                path: path.clone(),
+                error,
+            }
+        })?;
+        self.stack.push(path.clone());
+        self.loaded.files.push(path.clone());
+        for line in file.lines {
+            let source = Source {
+                path: path.clone(),
+                line_number: line.line_number,
+            };
+            match line.content {
+                LineContent::Empty => {}
+                LineContent::Requirement(requirement, options) => {
+                    self.loaded.requirements.push(LoadedRequirement {
+                        requirement,
+                        options,
+                        source,
+                        constraint,
+                    })
+                }
+                LineContent::Options(options) => {
+                    for option in options {
+                        // -r里的requirement不是constraint, -c里的都是
+                        let (value, nested_constraint) = match &option {
+                            PipOption::Requirement(value) => (value, false),
+                            PipOption::Constraint(value) => (value, true),
+                            _ => {
+                                self.loaded.options.push((option, source.clone()));
+                                continue;
+                            }
+                        };
+                        let include = include_path(&path, value, &source)?;
+                        self.load(include, nested_constraint, Some(source.clone()))?;
+                    }
+                }
+            }
+        }
+        self.stack.pop();
+        Ok(())
+    }
+}
+
+// 加载path和它直接或者间接引用的所有文件
+pub fn load_requirements<R: FileReader>(
+    reader: &R,
+    path: impl AsRef<Path>,
+) -> Result<LoadedRequirements, LoadError> {
+    load_requirements_with_env(reader, path, &EnvVars::default())
+}
+
+// 每个文件都先展开${VAR}再解析, -r/-c的路径中也可以用变量
+pub fn load_requirements_with_env<R: FileReader>(
+    reader: &R,
+    path: impl AsRef<Path>,
+    env: &EnvVars,
+) -> Result<LoadedRequirements, LoadError> {
+    let mut loader = Loader {
+        reader,
+        env,
+        stack: vec![],
+        loaded: LoadedRequirements::default(),
+    };
+    loader.load(normalize_path(path.as_ref()), false, None)?;
+    Ok(loader.loaded)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        path::{Path, PathBuf},
+    };
+
+    use super::{load_requirements, load_requirements_with_env, normalize_path, LoadError, Source};
+    use crate::{parser::requirements_file::EnvVars, requirements::PipOption};
+
+    fn files(files: &[(&str, &str)]) -> HashMap<PathBuf, String> {
+        files
+            .iter()
+            .map(|(path, content)| (PathBuf::from(path), content.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_normalize_path() {
+        assert_eq!(
+            normalize_path(Path::new("a/./b/../c.txt")),
+            Path::new("a/c.txt")
+        );
+        assert_eq!(
+            normalize_path(Path::new("../a/../../b")),
+            Path::new("../../b")
+        );
+        assert_eq!(normalize_path(Path::new("/../a")), Path::new("/a"));
+    }
+
+    #[test]
+    fn test_load_requirements() {
+        let reader = files(&[
+            (
+                "project/requirements.txt",
+                "--index-url https://pypi.org/simple
+-r requirements/base.txt
+-c constraints.txt
+flask==2.0 --hash=sha256:abcd
+",
+            ),
+            (
+                "project/requirements/base.txt",
+                "requests>=2\n-r ../common.txt\n",
+            ),
+            ("project/common.txt", "# shared\nsix\n"),
+            ("project/constraints.txt", "urllib3<2\n-r common.txt\n"),
+        ]);
+        let loaded = load_requirements(&reader, "project/requirements.txt").unwrap();
+        let summary: Vec<_> = loaded
+            .requirements
+            .iter()
+            .map(|r| {
+                (
+                    r.requirement.to_string(),
+                    r.source.to_string(),
+                    r.constraint,
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (
+                    "requests>=2".to_string(),
+                    "project/requirements/base.txt:1".to_string(),
+                    false
+                ),
+                ("six".to_string(), "project/common.txt:2".to_string(), false),
+                (
+                    "urllib3<2".to_string(),
+                    "project/constraints.txt:1".to_string(),
+                    true
+                ),
+                ("six".to_string(), "project/common.txt:2".to_string(), false),
+                (
+                    "flask==2.0".to_string(),
+                    "project/requirements.txt:4".to_string(),
+                    false
+                ),
+            ]
+        );
+        assert_eq!(loaded.requirements[4].options[0].name(), "--hash");
+        assert_eq!(
+            loaded.options,
+            vec![(
+                PipOption::IndexUrl("https://pypi.org/simple".to_string()),
+                Source {
+                    path: PathBuf::from("project/requirements.txt"),
+                    line_number: 1
+                }
+            )]
+        );
+        assert_eq!(loaded.files.len(), 5);
+    }
+
+    #[test]
+    fn test_load_errors() {
+        let reader = files(&[
+            ("a.txt", "-r b.txt\n"),
+            ("b.txt", "foo\n\n-r ./sub/../a.txt\n"),
+            ("c.txt", "-r missing.txt\n"),
+            ("d.txt", "foo >=\n"),
+            ("e.txt", "-r https://example.com/requirements.txt\n"),
+        ]);
+        let e = load_requirements(&reader, "a.txt").unwrap_err();
+        assert!(matches!(&e, LoadError::Cycle { chain, .. } if chain.len() == 3));
+        assert_eq!(
+            e.to_string(),
+            "b.txt:3: include cycle: a.txt -> b.txt -> a.txt"
+        );
+        let e = load_requirements(&reader, "c.txt").unwrap_err();
+        assert_eq!(e.to_string(), "c.txt:1: missing.txt: No such file");
+        let e = load_requirements(&reader, "d.txt").unwrap_err();
+        assert_eq!(e.to_string(), "d.txt:1:7: expected version, found '\\n'");
+        let e = load_requirements(&reader, "e.txt").unwrap_err();
+        assert!(matches!(e, LoadError::UnsupportedUrl { .. }));
+    }
+
+    #[test]
+    fn test_load_with_env() {
+        let reader = files(&[
+            ("req/main.txt", "-r ${ENV}.txt\n"),
+            (
+                "req/prod.txt",
+                "foo @ https://${TOKEN}@example.com/foo.zip\n",
+            ),
+        ]);
+        let env = EnvVars {
+            vars: [("ENV", "prod"), ("TOKEN", "secret")]
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            strict: true,
+        };
+        let loaded = load_requirements_with_env(&reader, "req/main.txt", &env).unwrap();
+        assert_eq!(loaded.files[1], PathBuf::from("req/prod.txt"));
+        assert_eq!(
+            loaded.requirements[0].requirement.to_string(),
+            "foo @ https://secret@example.com/foo.zip"
+        );
+        let e = load_requirements(&reader, "req/main.txt").unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "req/main.txt:1: req/${ENV}.txt: No such file"
+        );
+    }
+}
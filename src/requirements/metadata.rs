@@ -0,0 +1,237 @@
+//! 核心元数据(METADATA/PKG-INFO), 字段见<https://packaging.python.org/en/latest/specifications/core-metadata/>
+//! 只能出现一次的字段为Option, 可以出现多次的字段为Vec, 都按文件中的顺序
+use std::str::FromStr;
+
+use super::{
+    requires_python::RequiresPython, ExtraName, PackageName, RequirementSpecifier, Version,
+};
+use crate::{
+    error::ParseError,
+    parser::{core_metadata::core_metadata, lenient::LenientWarning},
+};
+
+pub static METADATA_VERSIONS: [&str; 8] = ["1.0", "1.1", "1.2", "2.0", "2.1", "2.2", "2.3", "2.4"];
+
+#[derive(Debug, PartialEq, Default)]
+pub struct CoreMetadata {
+    pub metadata_version: String,
+    pub name: PackageName,
+    pub version: Version,
+    pub summary: Option<String>,
+    // Description字段, 或者header之后的正文
+    pub description: Option<String>,
+    pub description_content_type: Option<String>,
+    pub keywords: Option<String>,
+    pub home_page: Option<String>,
+    pub download_url: Option<String>,
+    pub author: Option<String>,
+    pub author_email: Option<String>,
+    pub maintainer: Option<String>,
+    pub maintainer_email: Option<String>,
+    pub license: Option<String>,
+    pub license_expression: Option<String>,
+    pub license_files: Vec<String>,
+    pub classifiers: Vec<String>,
+    pub platforms: Vec<String>,
+    pub supported_platforms: Vec<String>,
+    // (label, url)
+    pub project_urls: Vec<(String, String)>,
+    // 按宽松模式解析, 修复的位置对应到整个文件
+    pub requires_dist: Vec<RequirementSpecifier>,
+    pub requires_dist_warnings: Vec<LenientWarning>,
+    // 宽松模式也无法解析的Requires-Dist: (原来的值, 错误)
+    pub invalid_requires_dist: Vec<(String, ParseError)>,
+    pub requires_python: Option<RequiresPython>,
+    // 无法解析的Requires-Python: (原来的值, 错误)
+    pub invalid_requires_python: Option<(String, ParseError)>,
+    pub requires_external: Vec<String>,
+    pub provides_extra: Vec<ExtraName>,
+    pub invalid_provides_extra: Vec<(String, ParseError)>,
+    pub provides_dist: Vec<String>,
+    pub obsoletes_dist: Vec<String>,
+    pub dynamic: Vec<String>,
+    // 其他字段, 如1.1中已经废弃的Requires, 以及重复的Requires-Python
+    pub other: Vec<(String, String)>,
+}
+
+impl FromStr for CoreMetadata {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        core_metadata(s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CoreMetadata;
+    use crate::parser::lenient::Recovery;
+    use crate::requirements::{ExtraName, MarkerEnvironment, PackageName};
+
+    static METADATA: &str = "Metadata-Version: 2.1\r
+Name: requests\r
+Version: 2.31.0\r
+Summary: Python HTTP for Humans.\r
+Home-page: https://requests.readthedocs.io\r
+License: Apache 2.0\r
+Project-URL: Source, https://github.com/psf/requests\r
+Classifier: Programming Language :: Python :: 3\r
+Classifier: Topic :: Software Development :: Libraries\r
+Requires-Python: >=3.7\r
+Description-Content-Type: text/markdown\r
+License-File: LICENSE\r
+Requires-Dist: charset-normalizer (<4,>=2)\r
+Requires-Dist: idna (<4,>=2.5)\r
+Requires-Dist: PySocks (!=1.5.7,>=1.5.6) ; extra == 'socks'\r
+Provides-Extra: socks\r
+Provides-Extra: use_chardet_on_py3\r
+\r
+# Requests\r
+\r
+**Requests** is a simple, yet elegant, HTTP library.\r
+";
+
+    #[test]
+    fn test_metadata_2_1() {
+        let m: CoreMetadata = METADATA.parse().unwrap();
+        assert_eq!(m.metadata_version, "2.1");
+        assert_eq!(m.name, PackageName::new("requests"));
+        assert_eq!(m.version.to_string(), "2.31.0");
+        assert_eq!(
+            m.project_urls,
+            [(
+                "Source".to_string(),
+                "https://github.com/psf/requests".to_string()
+            )]
+        );
+        assert_eq!(m.classifiers.len(), 2);
+        assert_eq!(m.requires_python.unwrap().to_string(), ">=3.7");
+        assert_eq!(
+            m.requires_dist
+                .iter()
+                .map(|r| r.to_string())
+                .collect::<Vec<_>>(),
+            [
+                "charset-normalizer<4,>=2",
+                "idna<4,>=2.5",
+                "PySocks!=1.5.7,>=1.5.6; extra == \"socks\""
+            ]
+        );
+        let env = MarkerEnvironment::default();
+        assert!(!m.requires_dist[2].evaluate(&env, &[] as &[&str]));
+        assert_eq!(
+            m.provides_extra,
+            [
+                ExtraName::new("socks"),
+                ExtraName::new("use-chardet-on-py3")
+            ]
+        );
+        assert_eq!(
+            m.description.as_deref(),
+            Some("# Requests\r\n\r\n**Requests** is a simple, yet elegant, HTTP library.\r\n")
+        );
+    }
+
+    #[test]
+    fn test_metadata_1_x() {
+        let content = "Metadata-Version: 1.1
+Name: Foo.Bar
+Version: 0.9b1
+Summary: legacy package
+Description: First line
+       |   indented
+        
+        last line
+Keywords: a b
+Requires: os
+Platform: UNKNOWN
+";
+        let m: CoreMetadata = content.parse().unwrap();
+        assert_eq!(m.name, PackageName::new("foo-bar"));
+        assert_eq!(
+            m.description.as_deref(),
+            Some("First line\n   indented\n\nlast line")
+        );
+        assert_eq!(m.other, [("Requires".to_string(), "os".to_string())]);
+        assert_eq!(m.platforms, ["UNKNOWN"]);
+    }
+
+    #[test]
+    fn test_lenient_requires_dist() {
+        let content = "Metadata-Version: 2.1
+Name: foo
+Version: 1.0
+Requires-Dist: bar (>=1.0<2)
+Requires-Dist: baz (
+Requires-Dist: qux ; extra == 'test'
+Requires-Python: >=3.8
+";
+        let m: CoreMetadata = content.parse().unwrap();
+        assert_eq!(m.name, PackageName::new("foo"));
+        assert_eq!(m.version.to_string(), "1.0");
+        assert_eq!(m.requires_python.unwrap().to_string(), ">=3.8");
+        let requires: Vec<_> = m.requires_dist.iter().map(|r| r.to_string()).collect();
+        assert_eq!(requires, ["bar>=1.0,<2", "qux; extra == \"test\""]);
+        assert_eq!(m.requires_dist_warnings.len(), 1);
+        assert_eq!(m.requires_dist_warnings[0].recovery, Recovery::MissingComma);
+        assert_eq!(&content[m.requires_dist_warnings[0].offset..][..1], "<");
+        assert_eq!(m.invalid_requires_dist.len(), 1);
+        let (value, e) = &m.invalid_requires_dist[0];
+        assert_eq!(value, "baz (");
+        assert_eq!(e.line, 5);
+    }
+
+    #[test]
+    fn test_invalid_fields() {
+        let content = "Metadata-Version: 1.2
+Name: foo
+Version: 1.0
+Requires-Python: >=3.6.*
+Requires-Python: >=3.6
+Provides-Extra: test
+Provides-Extra: -bad
+";
+        let m: CoreMetadata = content.parse().unwrap();
+        assert_eq!(m.requires_python, None);
+        let (value, e) = m.invalid_requires_python.unwrap();
+        assert_eq!((value.as_str(), e.line), (">=3.6.*", 4));
+        assert_eq!(
+            m.other,
+            [("Requires-Python".to_string(), ">=3.6".to_string())]
+        );
+        assert_eq!(m.provides_extra, [ExtraName::new("test")]);
+        assert_eq!(m.invalid_provides_extra.len(), 1);
+        let (value, e) = &m.invalid_provides_extra[0];
+        assert_eq!((value.as_str(), e.line, e.column), ("-bad", 7, 17));
+    }
+
+    #[test]
+    fn test_errors() {
+        for (content, message, line) in [
+            (
+                "Metadata-Version: 3.0\nName: foo\nVersion: 1.0\n",
+                "expected metadata version 1.0 to 2.4, found '3'",
+                1,
+            ),
+            (
+                "Metadata-Version: 2.1\nName: foo\n\nbody",
+                "expected Version field, found 'b'",
+                4,
+            ),
+            (
+                "Metadata-Version: 2.1\nName foo\n",
+                "expected ':', found ' '",
+                2,
+            ),
+            (" Name: foo\n", "expected field name, found ' '", 1),
+            (
+                "Metadata-Version: 2.1\nName: foo\nVersion: 1.0\nname: bar\n",
+                "expected a single name field, found 'n'",
+                4,
+            ),
+        ] {
+            let e = content.parse::<CoreMetadata>().unwrap_err();
+            assert_eq!((e.message.as_str(), e.line), (message, line), "{}", content);
+        }
+    }
+}
@@ -0,0 +1,213 @@
+//! pyproject.toml中的依赖, 见<https://packaging.python.org/en/latest/specifications/pyproject-toml/>
+//! 和PEP 735的dependency-groups, 见<https://packaging.python.org/en/latest/specifications/dependency-groups/>
+//! 每个依赖都记录在TOML中的位置, 用于提示或者原地修改
+use std::{ops::Range, str::FromStr};
+
+use super::{requires_python::RequiresPython, ExtraName, PackageName, RequirementSpecifier};
+use crate::{error::ParseError, parser::pyproject::pyproject};
+
+#[derive(Debug, PartialEq)]
+pub struct Spanned<T> {
+    pub value: T,
+    // 字符串字面量(包括引号)在源文本中的字节范围
+    pub span: Range<usize>,
+    // 从1开始
+    pub line: usize,
+}
+
+#[derive(Debug, PartialEq)]
+#[allow(clippy::large_enum_variant)]
+pub enum DependencyGroupItem {
+    Requirement(Spanned<RequirementSpecifier>),
+    // {include-group = "name"}
+    IncludeGroup(Spanned<PackageName>),
+}
+
+// PEP 735中组名的规范化和包名相同, 所以用PackageName
+#[derive(Debug, PartialEq, Default)]
+pub struct PyProject {
+    // project.requires-python
+    pub requires_python: Option<Spanned<RequiresPython>>,
+    // project.dependencies
+    pub dependencies: Vec<Spanned<RequirementSpecifier>>,
+    // project.optional-dependencies, 按文件中的顺序
+    pub optional_dependencies: Vec<(ExtraName, Vec<Spanned<RequirementSpecifier>>)>,
+    // build-system.requires
+    pub build_requires: Vec<Spanned<RequirementSpecifier>>,
+    // dependency-groups, 按文件中的顺序
+    pub dependency_groups: Vec<(PackageName, Vec<DependencyGroupItem>)>,
+}
+
+impl PyProject {
+    pub fn optional_dependencies(&self, extra: &str) -> Option<&[Spanned<RequirementSpecifier>]> {
+        let extra = ExtraName::new(extra);
+        self.optional_dependencies
+            .iter()
+            .find(|(name, _)| *name == extra)
+            .map(|(_, deps)| deps.as_slice())
+    }
+
+    // 展开include-group之后的依赖, 按PEP 735参考实现的resolve顺序
+    // 解析时已经检查了不存在的组和循环引用; 这里再跳过它们, 避免手动构造的PyProject死循环
+    pub fn dependency_group(&self, group: &str) -> Option<Vec<&Spanned<RequirementSpecifier>>> {
+        let group = PackageName::new(group);
+        let (_, items) = self.dependency_groups.iter().find(|(n, _)| *n == group)?;
+        let mut requirements = vec![];
+        self.resolve_group(items, &mut vec![group], &mut requirements);
+        Some(requirements)
+    }
+
+    fn resolve_group<'a>(
+        &'a self,
+        items: &'a [DependencyGroupItem],
+        stack: &mut Vec<PackageName>,
+        requirements: &mut Vec<&'a Spanned<RequirementSpecifier>>,
+    ) {
+        for item in items {
+            match item {
+                DependencyGroupItem::Requirement(req) => requirements.push(req),
+                DependencyGroupItem::IncludeGroup(include) => {
+                    if stack.contains(&include.value) {
+                        continue;
+                    }
+                    let Some((_, included)) = self
+                        .dependency_groups
+                        .iter()
+                        .find(|(n, _)| *n == include.value)
+                    else {
+                        continue;
+                    };
+                    stack.push(include.value.clone());
+                    self.resolve_group(included, stack, requirements);
+                    stack.pop();
+                }
+            }
+        }
+    }
+}
+
+impl FromStr for PyProject {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        pyproject(s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DependencyGroupItem, PyProject, Spanned};
+    use crate::requirements::{ExtraName, PackageName, RequirementSpecifier};
+
+    static PYPROJECT: &str = r#"[build-system]
+requires = ["setuptools>=61", 'wheel']
+build-backend = "setuptools.build_meta"
+
+[project]
+name = "demo"
+requires-python = ">=3.8"
+dependencies = [
+    "requests[socks] >=2.31",
+    "tomli; python_version < '3.11'",
+]
+
+[project.optional-dependencies]
+Test = ["pytest>=7"]
+docs = ["sphinx"]
+
+[dependency-groups]
+lint = ["ruff"]
+typing = ["mypy", {include-group = "lint"}]
+dev = [{include-group = "Typing"}, "pre-commit", {include-group = "lint"}]
+"#;
+
+    fn names(reqs: &[&Spanned<RequirementSpecifier>]) -> Vec<String> {
+        reqs.iter().map(|r| r.value.name.to_string()).collect()
+    }
+
+    #[test]
+    fn test_pyproject() {
+        let pyproject: PyProject = PYPROJECT.parse().unwrap();
+        let requires_python = pyproject.requires_python.as_ref().unwrap();
+        assert_eq!(requires_python.value.to_string(), ">=3.8");
+        assert_eq!(&PYPROJECT[requires_python.span.clone()], "\">=3.8\"");
+        assert_eq!(requires_python.line, 7);
+
+        let build: Vec<_> = pyproject.build_requires.iter().collect();
+        assert_eq!(names(&build), ["setuptools", "wheel"]);
+        assert_eq!(&PYPROJECT[build[1].span.clone()], "'wheel'");
+        assert_eq!(build[1].line, 2);
+
+        let deps = &pyproject.dependencies;
+        assert_eq!(deps.len(), 2);
+        assert_eq!(deps[0].value.extras, vec![ExtraName::new("socks")]);
+        assert_eq!(
+            &PYPROJECT[deps[1].span.clone()],
+            "\"tomli; python_version < '3.11'\""
+        );
+        assert_eq!((deps[0].line, deps[1].line), (9, 10));
+
+        let extras: Vec<_> = pyproject
+            .optional_dependencies
+            .iter()
+            .map(|(extra, _)| extra.as_str())
+            .collect();
+        assert_eq!(extras, ["Test", "docs"]);
+        assert_eq!(pyproject.optional_dependencies("test").unwrap().len(), 1);
+        assert!(pyproject.optional_dependencies("missing").is_none());
+
+        let DependencyGroupItem::IncludeGroup(include) = &pyproject.dependency_groups[1].1[1]
+        else {
+            panic!("expected include-group");
+        };
+        assert_eq!(include.value, PackageName::new("lint"));
+        assert_eq!(&PYPROJECT[include.span.clone()], "\"lint\"");
+        assert_eq!(include.line, 19);
+
+        let dev = pyproject.dependency_group("dev").unwrap();
+        assert_eq!(names(&dev), ["mypy", "ruff", "pre-commit", "ruff"]);
+        assert_eq!(
+            names(&pyproject.dependency_group("lint").unwrap()),
+            ["ruff"]
+        );
+        assert!(pyproject.dependency_group("missing").is_none());
+    }
+
+    #[test]
+    fn test_pyproject_empty() {
+        let pyproject: PyProject = "[tool.black]\nline-length = 100\n".parse().unwrap();
+        assert_eq!(pyproject, PyProject::default());
+    }
+
+    #[test]
+    fn test_pyproject_errors() {
+        for (input, line, column, expected) in [
+            // 依赖字符串中的错误对应到TOML中的位置
+            ("[project]\ndependencies = [\"foo >= \"]\n", 2, 25, "version"),
+            ("[project]\nrequires-python = '>=3.8,'\n", 2, 26, "version operator"),
+            ("[project]\ndependencies = \"foo\"\n", 2, 16, "array of strings"),
+            ("[project]\ndependencies = [\"foo\", 1]\n", 2, 24, "string"),
+            ("[build-system]\nrequires = [\"a b\"]\n", 2, 16, "version specifier, '[', '@' or ';'"),
+            ("[project.optional-dependencies]\n\"a b\" = []\n", 2, 1, "extra name"),
+            ("[dependency-groups]\ndev = [{include = \"x\"}]\n", 2, 8, "string or {include-group = ...}"),
+            ("[dependency-groups]\ndev = [{include-group = \"x\"}]\n", 2, 25, "existing dependency group"),
+            ("[dependency-groups]\nDev = []\ndev = []\n", 3, 1, "unique dependency group name"),
+            (
+                "[dependency-groups]\na = [{include-group = \"b\"}]\nb = [{include-group = \"a\"}]\n",
+                3,
+                23,
+                "acyclic include-group",
+            ),
+            ("[project\n", 1, 9, "valid TOML"),
+        ] {
+            let error = input.parse::<PyProject>().unwrap_err();
+            assert_eq!(
+                (error.line, error.column, error.expected.as_str()),
+                (line, column, expected),
+                "{:?}: {}",
+                input,
+                error
+            );
+        }
+    }
+}
@@ -0,0 +1,489 @@
+//! 把版本约束转换为PEP 440版本序上的区间, 用来求交集, 并集, 判断是否为空或者是否为子集
+//! 区间的端点不是版本而是版本之间的"切点"(Cut), 每个区间都是两个切点之间的所有版本, 不用区分开闭.
+//! 如 ==1.0 是 (Below(1.0), Above(1.0)), 包含1.0和1.0+local; <1.0 是 (NegInf, BelowRelease(1.0)), 不包含1.0rc1.
+//!
+//! 有几种约束无法用有限个区间精确表示, 这里用比实际更大的区间代替:
+//! - <V, V是post-release: 实际还排除了base相同的pre-releases, 如 <1.0.post1 不包含1.0.post0.dev0
+//! - >V, V不是final release: 实际还排除了base相同的local versions(V不是post-release时还有post-releases)
+//! - ===, 按字符串比较, 只有规范化的写法才能匹配; 不能解析为版本时当作任意版本
+//!
+//! 所以is_empty()返回true时一定没有版本满足约束, 反之不一定(比如 >1.0.dev0,<1.0.dev1 之间没有版本).
+//! 另外这里不考虑SpecifierSet中pre-releases的默认策略, 总是把pre-releases计算在内.
+use std::cmp::Ordering;
+
+use super::{Comparison, LocalVersionPart, SpecifierSet, Version, VersionPattern, VersionSpec};
+
+// 两个相邻版本之间的位置
+#[derive(Debug, Clone)]
+pub enum Cut {
+    NegInf,
+    // release等于version.release的所有版本之前(只用到epoch和release)
+    BelowRelease(Version),
+    // version之前
+    Below(Version),
+    // version之后; version没有local时, 也在version的所有local versions之后
+    Above(Version),
+    // release等于version.release的所有版本之后(只用到epoch和release)
+    AboveRelease(Version),
+    PosInf,
+}
+
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+enum LocalKey<'a> {
+    Absent,
+    Label(&'a Vec<LocalVersionPart>),
+    All,
+}
+
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+enum Tail<'a> {
+    Start,
+    // (pre, post, dev, local, 0: 之前 1: 版本本身 2: 之后)
+    At(
+        (&'a str, u64),
+        (&'a str, u64),
+        (&'a str, u64),
+        LocalKey<'a>,
+        u8,
+    ),
+    End,
+}
+
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+enum Position<'a> {
+    NegInf,
+    Finite(u64, Vec<u64>, Tail<'a>),
+    PosInf,
+}
+
+// 在Version::cmpkey的基础上加上切点的位置
+fn position(version: &Version, side: u8) -> Position<'_> {
+    let (epoch, release, pre, post, dev, local) = version.cmpkey();
+    // X.dev0是release为X的第一个版本, 它之前就是BelowRelease(X)
+    if side == 0
+        && version.pre.is_none()
+        && version.post.is_none()
+        && version.local.is_none()
+        && matches!(version.dev, Some((_, 0)))
+    {
+        return Position::Finite(epoch, release, Tail::Start);
+    }
+    let local = match (&version.local, side) {
+        (None, 2) => LocalKey::All,
+        (None, _) => LocalKey::Absent,
+        (Some(_), _) => LocalKey::Label(local),
+    };
+    Position::Finite(epoch, release, Tail::At(pre, post, dev, local, side))
+}
+
+impl Cut {
+    fn position(&self) -> Position<'_> {
+        match self {
+            Self::NegInf => Position::NegInf,
+            Self::BelowRelease(v) => {
+                Position::Finite(v.epoch, v.release_without_trailing_zero(), Tail::Start)
+            }
+            Self::Below(v) => position(v, 0),
+            Self::Above(v) => position(v, 2),
+            Self::AboveRelease(v) => {
+                Position::Finite(v.epoch, v.release_without_trailing_zero(), Tail::End)
+            }
+            Self::PosInf => Position::PosInf,
+        }
+    }
+}
+
+impl PartialEq for Cut {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Cut {}
+
+impl Ord for Cut {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.position().cmp(&other.position())
+    }
+}
+
+impl PartialOrd for Cut {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// 有序且互不相交的区间, 每个区间(lo, hi)都满足lo < hi
+#[derive(Debug, Clone, PartialEq)]
+pub struct VersionRange(Vec<(Cut, Cut)>);
+
+impl VersionRange {
+    pub fn full() -> Self {
+        Self(vec![(Cut::NegInf, Cut::PosInf)])
+    }
+
+    pub fn empty() -> Self {
+        Self(vec![])
+    }
+
+    fn between(lo: Cut, hi: Cut) -> Self {
+        if lo < hi {
+            Self(vec![(lo, hi)])
+        } else {
+            Self::empty()
+        }
+    }
+
+    pub fn intervals(&self) -> &[(Cut, Cut)] {
+        &self.0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn contains(&self, version: &Version) -> bool {
+        let p = position(version, 1);
+        self.0
+            .iter()
+            .any(|(lo, hi)| lo.position() < p && p < hi.position())
+    }
+
+    pub fn complement(&self) -> Self {
+        let mut intervals = vec![];
+        let mut prev = Cut::NegInf;
+        for (lo, hi) in &self.0 {
+            if prev < *lo {
+                intervals.push((prev, lo.clone()));
+            }
+            prev = hi.clone();
+        }
+        if prev < Cut::PosInf {
+            intervals.push((prev, Cut::PosInf));
+        }
+        Self(intervals)
+    }
+
+    pub fn intersect(&self, other: &Self) -> Self {
+        let mut intervals = vec![];
+        let (mut i, mut j) = (0, 0);
+        while i < self.0.len() && j < other.0.len() {
+            let (a, b) = (&self.0[i], &other.0[j]);
+            let lo = a.0.clone().max(b.0.clone());
+            let hi = a.1.clone().min(b.1.clone());
+            if lo < hi {
+                intervals.push((lo, hi));
+            }
+            // 先结束的区间不会再和后边的区间相交
+            if a.1 < b.1 {
+                i += 1;
+            } else {
+                j += 1;
+            }
+        }
+        Self(intervals)
+    }
+
+    pub fn union(&self, other: &Self) -> Self {
+        let mut all: Vec<&(Cut, Cut)> = self.0.iter().chain(other.0.iter()).collect();
+        all.sort_by(|a, b| a.0.cmp(&b.0));
+        let mut intervals: Vec<(Cut, Cut)> = vec![];
+        for (lo, hi) in all {
+            match intervals.last_mut() {
+                // 相接的区间也要合并, 切点上没有版本
+                Some(last) if *lo <= last.1 => {
+                    if *hi > last.1 {
+                        last.1 = hi.clone();
+                    }
+                }
+                _ => intervals.push((lo.clone(), hi.clone())),
+            }
+        }
+        Self(intervals)
+    }
+
+    pub fn is_subset_of(&self, other: &Self) -> bool {
+        self.intersect(&other.complement()).is_empty()
+    }
+}
+
+fn release_version(epoch: u64, release: &[u64]) -> Version {
+    Version {
+        epoch,
+        release: release.to_vec(),
+        ..Default::default()
+    }
+}
+
+// 前缀匹配X.*的范围: 从release为X的第一个版本开始, 到release为X的下一个前缀的第一个版本之前
+fn prefix_range(epoch: u64, prefix: &[u64]) -> VersionRange {
+    let mut next = prefix.to_vec();
+    match next.last_mut() {
+        Some(last) => *last += 1,
+        None => return VersionRange::full(),
+    }
+    VersionRange::between(
+        Cut::BelowRelease(release_version(epoch, prefix)),
+        Cut::BelowRelease(release_version(epoch, &next)),
+    )
+}
+
+fn is_final(v: &Version) -> bool {
+    v.pre.is_none() && v.post.is_none() && v.dev.is_none()
+}
+
+impl VersionSpec {
+    // 满足约束的所有版本(不考虑pre-releases的默认策略), 见模块开头的说明
+    pub fn range(&self) -> VersionRange {
+        let (v, wildcard) = match &self.1 {
+            VersionPattern::Arbitrary(s) => {
+                if self.0 != Comparison::ArbitraryEqual {
+                    return VersionRange::empty();
+                }
+                return match s.parse::<Version>() {
+                    Ok(v) => VersionRange::between(Cut::Below(v.clone()), Cut::Above(v)),
+                    Err(_) => VersionRange::full(),
+                };
+            }
+            VersionPattern::Version { version, wildcard } => (version, *wildcard),
+        };
+        let point = || VersionRange::between(Cut::Below(v.clone()), Cut::Above(v.clone()));
+        match self.0 {
+            Comparison::Equal if wildcard => prefix_range(v.epoch, &v.release),
+            Comparison::NotEqual if wildcard => prefix_range(v.epoch, &v.release).complement(),
+            Comparison::Equal | Comparison::ArbitraryEqual => point(),
+            Comparison::NotEqual => point().complement(),
+            Comparison::LessThanOrEqual => {
+                VersionRange::between(Cut::NegInf, Cut::Above(v.clone()))
+            }
+            Comparison::GreaterThanOrEqual => {
+                VersionRange::between(Cut::Below(v.clone()), Cut::PosInf)
+            }
+            // <V不包含V的pre-releases, 除非V本身是pre-release
+            Comparison::LessThan if is_final(v) => {
+                VersionRange::between(Cut::NegInf, Cut::BelowRelease(v.clone()))
+            }
+            Comparison::LessThan => VersionRange::between(Cut::NegInf, Cut::Below(v.clone())),
+            // >V不包含V的post-releases和local versions
+            Comparison::GreaterThan if is_final(v) => {
+                VersionRange::between(Cut::AboveRelease(v.clone()), Cut::PosInf)
+            }
+            Comparison::GreaterThan => VersionRange::between(Cut::Above(v.clone()), Cut::PosInf),
+            // ~=V 相当于 >=V, ==V去掉最后一位.*
+            Comparison::CompatibleRelease => {
+                let prefix = &v.release[..v.release.len().saturating_sub(1)];
+                VersionRange::between(Cut::Below(v.clone()), Cut::PosInf)
+                    .intersect(&prefix_range(v.epoch, prefix))
+            }
+        }
+    }
+
+    // range()是否和contains()完全一致
+    fn is_exact_range(&self) -> bool {
+        match (&self.0, &self.1) {
+            (Comparison::ArbitraryEqual, _) => false,
+            (_, VersionPattern::Arbitrary(_)) => true,
+            (Comparison::LessThan, VersionPattern::Version { version, .. }) => {
+                is_final(version) || version.is_prerelease()
+            }
+            (Comparison::GreaterThan, VersionPattern::Version { version, .. }) => is_final(version),
+            _ => true,
+        }
+    }
+}
+
+impl SpecifierSet {
+    // 所有约束的交集
+    pub fn range(&self) -> VersionRange {
+        self.0
+            .iter()
+            .fold(VersionRange::full(), |r, spec| r.intersect(&spec.range()))
+    }
+
+    // 合并两组约束并去掉多余的约束
+    pub fn intersect(&self, other: &Self) -> Self {
+        let mut specs = self.0.clone();
+        specs.extend(other.0.iter().cloned());
+        Self(specs).simplify()
+    }
+
+    // 去掉被其他约束蕴含的约束, 如 >=1,>=2,!=0.5 => >=2; 保持原来的顺序
+    // 只去掉range()精确的约束, 保证结果和原来等价
+    pub fn simplify(&self) -> Self {
+        let mut specs = self.0.clone();
+        let mut i = 0;
+        while i < specs.len() {
+            let others = specs
+                .iter()
+                .enumerate()
+                .filter(|(j, _)| *j != i)
+                .fold(VersionRange::full(), |r, (_, spec)| {
+                    r.intersect(&spec.range())
+                });
+            if specs[i].is_exact_range() && others.is_subset_of(&specs[i].range()) {
+                specs.remove(i);
+            } else {
+                i += 1;
+            }
+        }
+        Self(specs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::VersionRange;
+    use crate::requirements::{SpecifierSet, Version, VersionSpec};
+
+    fn set(s: &str) -> SpecifierSet {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_range_matches_contains() {
+        let versions = [
+            "0.9",
+            "1!0.5",
+            "1.0.dev0",
+            "1.0a1",
+            "1.0a1+local",
+            "1.0rc1.post1",
+            "1.0",
+            "1.0+local",
+            "1.0.0",
+            "1.0.post0.dev0",
+            "1.0.post1",
+            "1.0.post1+local",
+            "1.0.1",
+            "1.0.1a1",
+            "1.0.5",
+            "1.1.dev0",
+            "1.1",
+            "1.1.post1",
+            "1.4",
+            "1.4.5",
+            "1.5.dev0",
+            "1.5",
+            "2.0a1",
+            "2",
+            "2.0.0.1",
+            "2.1",
+            "10",
+        ]
+        .map(|v| v.parse::<Version>().unwrap());
+        let specs = [
+            "==1.0",
+            "==1.0+local",
+            "==1.0.*",
+            "==1.*",
+            "==1!0.*",
+            "!=1.0",
+            "!=1.0.*",
+            "<1.0",
+            "<1.0a1",
+            "<=1.0",
+            ">1.0",
+            ">1.0.1",
+            ">=1.0",
+            ">=1.0a1",
+            "~=1.0",
+            "~=1.4.2",
+            "~=1.0a1",
+            "~=1!0.4",
+            "<1.0.post1",
+            ">1.0a1",
+            ">1.0.post0",
+            "===1.0",
+            "===foo",
+        ];
+        for spec in specs {
+            let spec: VersionSpec = spec.parse().unwrap();
+            let range = spec.range();
+            for v in &versions {
+                if spec.is_exact_range() {
+                    assert_eq!(range.contains(v), spec.matches(v), "{} {}", spec, v);
+                } else if spec.matches(v) {
+                    assert!(range.contains(v), "{} {}", spec, v);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_is_empty() {
+        for (s, empty) in [
+            ("", false),
+            (">=3,<2", true),
+            (">=1.0,<=1.0", false),
+            (">1.0,<=1.0", true),
+            (">1.0,<1.0.0.1", false),
+            (">1.0,<1.0.post1", true),
+            ("==1.0,!=1.0", true),
+            ("==1.0+local,!=1.0", true),
+            ("==1.0,!=1.0+local", false),
+            ("==1.5,!=1.*", true),
+            ("==1.5,!=1.4.*", false),
+            ("~=1.4.2,<1.4.2", true),
+            ("~=1.4.2,>=1.5", true),
+            ("~=1.4,>=1.5", false),
+            ("~=2.2,==2.*,!=2.2.*,<3.0.dev0", false),
+            ("<2.0,>=2.0a1", true),
+            ("<2.0a1,>=2.0.dev0", false),
+            ("!=1.0,===1.0", true),
+        ] {
+            assert_eq!(set(s).range().is_empty(), empty, "{}", s);
+        }
+    }
+
+    #[test]
+    fn test_union_and_subset() {
+        let a = set(">=1.0,<2.0").range();
+        let b = set(">=1.5,<3.0").range();
+        let c = set("<1.0").range();
+        assert_eq!(a.union(&b), set(">=1.0,<3.0").range());
+        assert_eq!(a.intersect(&b), set(">=1.5,<2.0").range());
+        // 1.0的pre-releases不在其中
+        assert_eq!(c.union(&a).intervals().len(), 2);
+        assert_eq!(
+            set("<1.0a1").range().union(&set(">=1.0a1,<2.0").range()),
+            set("<2.0").range()
+        );
+        assert_eq!(set("<1.0.dev0").range(), c);
+        assert_eq!(a.union(&a.complement()), VersionRange::full());
+        assert!(a.intersect(&a.complement()).is_empty());
+        assert_eq!(set("!=1.5").range().complement(), set("==1.5").range());
+        assert!(set("==1.5").range().is_subset_of(&a));
+        assert!(set("~=1.5.1").range().is_subset_of(&set("==1.5.*").range()));
+        assert!(set("==1.5.*").range().is_subset_of(&set("~=1.4").range()));
+        assert!(!set("~=1.5").range().is_subset_of(&set("==1.5.*").range()));
+        assert!(!a.is_subset_of(&b));
+        assert!(VersionRange::empty().is_subset_of(&VersionRange::empty()));
+        let u = a.union(&set(">=4").range());
+        assert_eq!(u.intervals().len(), 2);
+        assert!(u.contains(&"5".parse().unwrap()));
+        assert!(!u.contains(&"3".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_simplify() {
+        for (s, expected) in [
+            (">=1,>=2", ">=2"),
+            (">=1,>=1.0", ">=1.0"),
+            (">=1,<3,<2", ">=1,<2"),
+            ("~=1.4,>=1.5,<2", ">=1.5,<2"),
+            ("~=1.4,>=1.5", "~=1.4,>=1.5"),
+            ("==1.5,>=1,!=1.4,<2", "==1.5"),
+            ("!=1.4.*,!=1.4.1", "!=1.4.*"),
+            (">=1.0.dev0,==1.0.*", "==1.0.*"),
+            (">=1.0,==1.0.*", ">=1.0,==1.0.*"),
+            // 不精确的约束不会被去掉
+            ("<1.0.post1,<2", "<1.0.post1"),
+            ("<2,<1.0.post1", "<1.0.post1"),
+            (">=3,<2", ">=3,<2"),
+            ("", ""),
+        ] {
+            assert_eq!(set(s).simplify(), set(expected), "{}", s);
+        }
+        assert_eq!(set(">=1,<3").intersect(&set(">=2,!=5")), set("<3,>=2"));
+    }
+}
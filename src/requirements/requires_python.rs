@@ -0,0 +1,160 @@
+//! Requires-Python, 见<https://packaging.python.org/en/latest/specifications/core-metadata/#requires-python>
+//! 只有versionspec, 没有名字; 和pip中utils/packaging.py的check_requires_python一样, 总是允许pre-releases
+//! 比较时用python_full_version(三段), 而不是python_version(只有两段, 3.8.1的python_version是3.8)
+use std::{fmt::Display, str::FromStr};
+
+use super::{range::Cut, MarkerExpr, MarkerOp, MarkerValue, SpecifierSet, Version, VersionSpec};
+use crate::error::ParseError;
+
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct RequiresPython(pub SpecifierSet);
+
+impl RequiresPython {
+    pub fn version_specs(&self) -> &[VersionSpec] {
+        &self.0 .0
+    }
+
+    // python_full_version, 如 3.8.10; 去掉local version, 如从源码构建的3.12.0+
+    pub fn contains(&self, python_full_version: &str) -> bool {
+        // 末尾单独的'+'不是合法的local version
+        match python_full_version
+            .trim()
+            .trim_end_matches('+')
+            .parse::<Version>()
+        {
+            Ok(version) => self.matches(&version),
+            Err(_) => false,
+        }
+    }
+
+    pub fn matches(&self, python_full_version: &Version) -> bool {
+        let version = Version {
+            local: None,
+            ..python_full_version.clone()
+        };
+        self.0.matches(&version, Some(true))
+    }
+
+    // 支持的最低的(major, minor), 如 >=3.8.1,!=3.9.* => (3, 8)
+    // 没有下界时为None; 按VersionRange计算, 和VersionRange::is_empty一样可能不准确
+    pub fn lowest_minor(&self) -> Option<(u64, u64)> {
+        let range = self.0.range();
+        let (lo, _) = range.intervals().first()?;
+        let version = match lo {
+            Cut::NegInf | Cut::PosInf => return None,
+            Cut::BelowRelease(v) | Cut::Below(v) | Cut::Above(v) | Cut::AboveRelease(v) => v,
+        };
+        let major = version.release.first().copied().unwrap_or(0);
+        let minor = version.release.get(1).copied().unwrap_or(0);
+        Some((major, minor))
+    }
+
+    // 等价的marker, 如 >=3.8,!=3.9.* => python_full_version >= "3.8" and python_full_version != "3.9.*"
+    // 没有任何约束时为None
+    pub fn to_marker(&self) -> Option<MarkerExpr> {
+        self.version_specs()
+            .iter()
+            .map(|VersionSpec(op, pattern)| {
+                MarkerExpr::Basic(
+                    MarkerValue::Variable("python_full_version".to_string()),
+                    MarkerOp::Comparison(*op),
+                    MarkerValue::Literal(pattern.to_string()),
+                )
+            })
+            .reduce(|lhs, rhs| MarkerExpr::And(Box::new(lhs), Box::new(rhs)))
+    }
+}
+
+impl From<SpecifierSet> for RequiresPython {
+    fn from(specs: SpecifierSet) -> Self {
+        Self(specs)
+    }
+}
+
+impl Display for RequiresPython {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+// 空字符串表示支持所有版本
+impl FromStr for RequiresPython {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse().map(Self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RequiresPython;
+    use crate::requirements::MarkerEnvironment;
+
+    fn rp(s: &str) -> RequiresPython {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_contains() {
+        for (spec, version, contains) in [
+            (">=3.8", "3.8.0", true),
+            (">=3.8", "3.8", true),
+            (">=3.8", "3.7.17", false),
+            // pre-releases总是允许
+            (">=3.8", "3.13.0rc1", true),
+            (">=3.8.1", "3.8.0", false),
+            ("~=3.8", "3.12.1", true),
+            ("~=3.8.0", "3.9.0", false),
+            ("!=3.9.*", "3.9.7", false),
+            ("<3.12", "3.12.0+", false),
+            (">=3.8", "3.12.0+", true),
+            ("==3.11.*", "3.11.4+local", true),
+            ("", "2.7.18", true),
+            (">=3.8", "not a version", false),
+        ] {
+            assert_eq!(rp(spec).contains(version), contains, "{} {}", spec, version);
+        }
+    }
+
+    #[test]
+    fn test_lowest_minor() {
+        for (spec, lowest) in [
+            (">=3.8", Some((3, 8))),
+            (">3.8", Some((3, 8))),
+            (">=3.8.1,!=3.9.*", Some((3, 8))),
+            (">=3", Some((3, 0))),
+            ("~=3.10", Some((3, 10))),
+            ("!=3.8.*,>=3.8", Some((3, 9))),
+            (">=2.7,!=3.0.*,!=3.1.*", Some((2, 7))),
+            ("<3.12", None),
+            ("", None),
+            (">=3.12,<3.8", None),
+        ] {
+            assert_eq!(rp(spec).lowest_minor(), lowest, "{}", spec);
+        }
+    }
+
+    #[test]
+    fn test_to_marker() {
+        let requires_python = rp(">=3.8.1, !=3.9.*, <4");
+        let marker = requires_python.to_marker().unwrap();
+        assert_eq!(
+            marker.to_string(),
+            "python_full_version >= \"3.8.1\" and python_full_version != \"3.9.*\" and python_full_version < \"4\""
+        );
+        for version in ["3.8.0", "3.8.1", "3.9.5", "3.10.0", "4.0.0", "3.13.0rc1"] {
+            let env = MarkerEnvironment {
+                python_full_version: version.to_string(),
+                ..Default::default()
+            };
+            assert_eq!(
+                marker.evaluate(&env, &[] as &[&str]),
+                requires_python.contains(version),
+                "{}",
+                version
+            );
+        }
+        assert_eq!(rp("").to_marker(), None);
+    }
+}
@@ -0,0 +1,144 @@
+//! sdist文件名, 见<https://packaging.python.org/en/latest/specifications/source-distribution-format/>
+//! 除了标准的.tar.gz, 也支持pip仍然接受的.zip, .tar.bz2和.tgz
+use std::{fmt::Display, str::FromStr};
+
+use super::{PackageName, Version};
+use crate::{
+    error::{parse_complete, ParseError},
+    parser::sdist_filename::{sdist_filename, split_sdist_filename},
+};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum SdistFormat {
+    TarGz,
+    Zip,
+    TarBz2,
+    Tgz,
+}
+
+impl SdistFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::TarGz => ".tar.gz",
+            Self::Zip => ".zip",
+            Self::TarBz2 => ".tar.bz2",
+            Self::Tgz => ".tgz",
+        }
+    }
+
+    // 返回(去掉扩展名的部分, 格式), 扩展名不区分大小写
+    pub fn split_extension(filename: &str) -> Option<(&str, Self)> {
+        [Self::TarGz, Self::Zip, Self::TarBz2, Self::Tgz]
+            .into_iter()
+            .find_map(|format| {
+                let ext = format.extension();
+                let i = filename.len().checked_sub(ext.len())?;
+                filename
+                    .get(i..)
+                    .filter(|e| e.eq_ignore_ascii_case(ext))
+                    .map(|_| (&filename[..i], format))
+            })
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct SdistFilename {
+    pub name: PackageName,
+    pub version: Version,
+    pub format: SdistFormat,
+}
+
+impl SdistFilename {
+    // 已知项目名时按名字拆分, 如 foo-2-1.0.tar.gz 中的项目可能是foo-2
+    pub fn parse_with_name(s: &str, name: &PackageName) -> Option<Self> {
+        split_sdist_filename(s, Some(name))
+    }
+}
+
+impl Display for SdistFilename {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}-{}{}",
+            self.name,
+            self.version,
+            self.format.extension()
+        )
+    }
+}
+
+impl FromStr for SdistFilename {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_complete(s, sdist_filename, "end of input")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SdistFilename, SdistFormat};
+    use crate::requirements::PackageName;
+
+    #[test]
+    fn test_sdist_filename() {
+        for (s, name, version, format) in [
+            (
+                "foo_bar-1.2.3.post1.tar.gz",
+                "foo-bar",
+                "1.2.3.post1",
+                SdistFormat::TarGz,
+            ),
+            (
+                "django-rest-framework-3.14.0.zip",
+                "django-rest-framework",
+                "3.14.0",
+                SdistFormat::Zip,
+            ),
+            (
+                "py2neo-2021.2.4.tar.bz2",
+                "py2neo",
+                "2021.2.4",
+                SdistFormat::TarBz2,
+            ),
+            ("Foo-2-1.0.TGZ", "foo-2", "1.0", SdistFormat::Tgz),
+            ("foo-1.0-1.tar.gz", "foo", "1.0.post1", SdistFormat::TarGz),
+            (
+                "foo-1.0rc1.dev3.tar.gz",
+                "foo",
+                "1.0rc1.dev3",
+                SdistFormat::TarGz,
+            ),
+        ] {
+            let sdist: SdistFilename = s.parse().unwrap();
+            assert_eq!(sdist.name, PackageName::new(name), "{}", s);
+            assert_eq!(sdist.version.to_string(), version, "{}", s);
+            assert_eq!(sdist.format, format, "{}", s);
+        }
+        assert_eq!(
+            SdistFilename::parse_with_name("foo-1-2.tar.gz", &PackageName::new("foo-1"))
+                .unwrap()
+                .to_string(),
+            "foo-1-2.tar.gz"
+        );
+        assert_eq!(
+            "foo-1-2.tar.gz"
+                .parse::<SdistFilename>()
+                .unwrap()
+                .to_string(),
+            "foo-1.post2.tar.gz"
+        );
+
+        for (s, message) in [
+            (
+                "foo-1.0.whl",
+                "expected '.tar.gz', '.zip', '.tar.bz2' or '.tgz', found end of input",
+            ),
+            ("foo.tar.gz", "expected '-', found '.'"),
+            ("-1.0.tar.gz", "expected project name, found '-'"),
+            ("foo-bar.tar.gz", "expected version, found 'b'"),
+        ] {
+            assert_eq!(s.parse::<SdistFilename>().unwrap_err().message, message);
+        }
+    }
+}
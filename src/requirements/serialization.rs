@@ -0,0 +1,245 @@
+//! serde支持, 需要启用serde feature
+//! 默认按字符串序列化: Version为规范化的版本号, VersionSpec如">=1.0", RequirementSpecifier为PEP 508文本;
+//! 反序列化时按严格模式解析.
+//! 需要结构化的形式时用StructuredRequirement, 或者在字段上加
+//! #[serde(with = "pyreq_rs::requirements::serialization::structured")]
+use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+
+use super::{
+    url::ParsedUrl, Comparison, ExtraName, MarkerExpr, MarkerOp, MarkerValue, PackageName,
+    RequirementSpecifier, SpecifierSet, VcsUrl, Version, VersionSpec,
+};
+
+// 通过Display和FromStr实现
+macro_rules! serde_as_str {
+    ($($t:ty),*) => {
+        $(
+            impl Serialize for $t {
+                fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                    serializer.collect_str(self)
+                }
+            }
+
+            impl<'de> Deserialize<'de> for $t {
+                fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                    String::deserialize(deserializer)?
+                        .parse()
+                        .map_err(D::Error::custom)
+                }
+            }
+        )*
+    };
+}
+
+serde_as_str!(
+    Comparison,
+    MarkerOp,
+    MarkerExpr,
+    Version,
+    VersionSpec,
+    SpecifierSet,
+    PackageName,
+    ExtraName,
+    RequirementSpecifier,
+    ParsedUrl,
+    VcsUrl
+);
+
+// MarkerValue按{"variable": "python_version"}或者{"literal": "3.8"}序列化
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "MarkerValue", rename_all = "lowercase")]
+enum MarkerValueDef {
+    Variable(String),
+    Literal(String),
+}
+
+// 结构化的marker, 如
+// {"and": [{"basic": {"lhs": {"variable": "python_version"}, "op": "<", "rhs": {"literal": "3.8"}}}, ...]}
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StructuredMarker {
+    Basic {
+        #[serde(with = "MarkerValueDef")]
+        lhs: MarkerValue,
+        op: MarkerOp,
+        #[serde(with = "MarkerValueDef")]
+        rhs: MarkerValue,
+    },
+    And(Box<Self>, Box<Self>),
+    Or(Box<Self>, Box<Self>),
+}
+
+impl From<&MarkerExpr> for StructuredMarker {
+    fn from(marker: &MarkerExpr) -> Self {
+        match marker {
+            MarkerExpr::Basic(lhs, op, rhs) => Self::Basic {
+                lhs: lhs.clone(),
+                op: op.clone(),
+                rhs: rhs.clone(),
+            },
+            MarkerExpr::And(lhs, rhs) => {
+                Self::And(Box::new(lhs.as_ref().into()), Box::new(rhs.as_ref().into()))
+            }
+            MarkerExpr::Or(lhs, rhs) => {
+                Self::Or(Box::new(lhs.as_ref().into()), Box::new(rhs.as_ref().into()))
+            }
+        }
+    }
+}
+
+impl From<StructuredMarker> for MarkerExpr {
+    fn from(marker: StructuredMarker) -> Self {
+        match marker {
+            StructuredMarker::Basic { lhs, op, rhs } => Self::Basic(lhs, op, rhs),
+            StructuredMarker::And(lhs, rhs) => {
+                Self::And(Box::new((*lhs).into()), Box::new((*rhs).into()))
+            }
+            StructuredMarker::Or(lhs, rhs) => {
+                Self::Or(Box::new((*lhs).into()), Box::new((*rhs).into()))
+            }
+        }
+    }
+}
+
+// 结构化的RequirementSpecifier, 名字和版本约束仍然是字符串
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct StructuredRequirement {
+    pub name: PackageName,
+    #[serde(default)]
+    pub extras: Vec<ExtraName>,
+    #[serde(default)]
+    pub version_specs: Vec<VersionSpec>,
+    #[serde(default)]
+    pub url: Option<ParsedUrl>,
+    #[serde(default)]
+    pub marker: Option<StructuredMarker>,
+}
+
+impl From<&RequirementSpecifier> for StructuredRequirement {
+    fn from(req: &RequirementSpecifier) -> Self {
+        Self {
+            name: req.name.clone(),
+            extras: req.extras.clone(),
+            version_specs: req.version_specs.clone(),
+            url: req.urlspec.clone(),
+            marker: req.marker_expr.as_ref().map(|m| m.into()),
+        }
+    }
+}
+
+impl From<StructuredRequirement> for RequirementSpecifier {
+    fn from(req: StructuredRequirement) -> Self {
+        Self {
+            name: req.name,
+            extras: req.extras,
+            version_specs: req.version_specs,
+            urlspec: req.url,
+            marker_expr: req.marker.map(|m| m.into()),
+        }
+    }
+}
+
+// 用于#[serde(with = "...")]
+pub mod structured {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::StructuredRequirement;
+    use crate::requirements::RequirementSpecifier;
+
+    pub fn serialize<S: Serializer>(
+        req: &RequirementSpecifier,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        StructuredRequirement::from(req).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<RequirementSpecifier, D::Error> {
+        StructuredRequirement::deserialize(deserializer).map(|r| r.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+    use serde_json::json;
+
+    use super::StructuredRequirement;
+    use crate::requirements::{MarkerExpr, RequirementSpecifier, Version, VersionSpec};
+
+    #[test]
+    fn test_string_form() {
+        let req: RequirementSpecifier = "Name [fred,bar] >= 1.0.PoSt1, <2 ; python_version=='2.7'"
+            .parse()
+            .unwrap();
+        let value = serde_json::to_value(&req).unwrap();
+        assert_eq!(
+            value,
+            json!("Name[bar,fred]>=1.0.post1,<2; python_version == \"2.7\"")
+        );
+        assert_eq!(
+            serde_json::from_value::<RequirementSpecifier>(value).unwrap(),
+            req
+        );
+        let version: Version = "v1.0-RC1".parse().unwrap();
+        assert_eq!(serde_json::to_value(&version).unwrap(), json!("1.0rc1"));
+        assert_eq!(
+            serde_json::from_value::<Vec<VersionSpec>>(json!([">=1.0", "==2.*"])).unwrap(),
+            vec![">=1.0".parse().unwrap(), "==2.*".parse().unwrap()]
+        );
+        let e = serde_json::from_value::<VersionSpec>(json!(">=1.0.*")).unwrap_err();
+        assert_eq!(e.to_string(), "1:6: expected end of input, found '.'");
+    }
+
+    #[test]
+    fn test_structured_form() {
+        let req: RequirementSpecifier =
+            "name[quux]>=1; os_name=='a' and (os_name=='b' or extra=='c')"
+                .parse()
+                .unwrap();
+        let value = serde_json::to_value(StructuredRequirement::from(&req)).unwrap();
+        assert_eq!(
+            value,
+            json!({
+                "name": "name",
+                "extras": ["quux"],
+                "version_specs": [">=1"],
+                "url": null,
+                "marker": {"and": [
+                    {"basic": {"lhs": {"variable": "os_name"}, "op": "==", "rhs": {"literal": "a"}}},
+                    {"or": [
+                        {"basic": {"lhs": {"variable": "os_name"}, "op": "==", "rhs": {"literal": "b"}}},
+                        {"basic": {"lhs": {"variable": "extra"}, "op": "==", "rhs": {"literal": "c"}}}
+                    ]}
+                ]}
+            })
+        );
+        assert_eq!(
+            RequirementSpecifier::from(
+                serde_json::from_value::<StructuredRequirement>(value).unwrap()
+            ),
+            req
+        );
+
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Cache {
+            #[serde(with = "super::structured")]
+            requirement: RequirementSpecifier,
+            marker: MarkerExpr,
+        }
+        let cache: Cache = serde_json::from_value(json!({
+            "requirement": {
+                "name": "A.B",
+                "marker": {"basic": {"lhs": {"variable": "os_name"}, "op": "not in", "rhs": {"literal": "nt"}}}
+            },
+            "marker": "python_version >= '3'"
+        }))
+        .unwrap();
+        assert_eq!(
+            cache.requirement,
+            "a-b; os_name not in 'nt'".parse().unwrap()
+        );
+        assert_eq!(cache.marker.to_string(), "python_version >= \"3\"");
+    }
+}
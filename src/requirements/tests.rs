@@ -1,8 +1,15 @@
 use std::cmp::Ordering;
 
 use crate::{
-    parser::{requirement_specifier::version_one, version::version_scheme},
-    requirements::LocalVersionPart,
+    parser::{
+        requirement_specifier::{specification, version_one},
+        version::version_scheme,
+    },
+    requirements::{
+        normalize_name, Comparison, ExtraName, LocalVersionPart, MarkerEnvironment, MarkerExpr,
+        PackageName, RequirementSpecifier, SpecifierSet, VcsUrl, VersionControlSystem,
+        VersionPattern, VersionSpec,
+    },
 };
 
 use super::Version;
@@ -47,6 +54,7 @@ fn test_local_version_part_ordering() {
 }
 
 #[test]
+#[allow(clippy::nonminimal_bool, clippy::needless_range_loop)]
 fn test_version_ordering() {
     // https://github.com/pypa/packaging/blob/main/tests/test_version.py
     let versions = [
@@ -341,3 +349,381 @@ fn test_spec_contains_version() {
         assert!(!s.contains(ver));
     }
 }
+
+fn linux_env() -> MarkerEnvironment {
+    MarkerEnvironment {
+        implementation_name: "cpython".to_string(),
+        implementation_version: "3.10.4".to_string(),
+        os_name: "posix".to_string(),
+        platform_machine: "x86_64".to_string(),
+        platform_release: "5.15.0-58-generic".to_string(),
+        platform_system: "Linux".to_string(),
+        platform_version: "#64-Ubuntu SMP Thu Jan 5 11:43:13 UTC 2023".to_string(),
+        python_full_version: "3.10.4".to_string(),
+        platform_python_implementation: "CPython".to_string(),
+        python_version: "3.10".to_string(),
+        sys_platform: "linux".to_string(),
+    }
+}
+
+#[test]
+fn test_marker_evaluate() {
+    let env = linux_env();
+    let no_extras: &[&str] = &[];
+    let matches = [
+        "a; python_version >= '3.8'",
+        // 按version比较, 而不是字符串比较
+        "a; python_version > '3.9'",
+        "a; '3.11' > python_version",
+        "a; python_full_version == '3.10.*'",
+        "a; sys_platform == 'linux' and platform_machine == 'x86_64'",
+        "a; sys_platform == 'win32' or os_name == 'posix'",
+        "a; 'linux' in sys_platform",
+        "a; 'Ubuntu' in platform_version",
+        "a; platform_release >= '5'",
+        "a; implementation_name not in 'pypy jython'",
+        "a; extra != 'test'",
+        "a; platform_system < 'Windows'",
+    ];
+    let not_matches = [
+        "a; python_version < '3.8'",
+        "a; python_version == '3.1'",
+        "a; sys_platform == 'win32'",
+        "a; sys_platform == 'linux' and python_version < '3'",
+        "a; 'win' in sys_platform",
+        "a; extra == 'test'",
+        "a; platform_system ~= 'Linux'",
+        // 带引号的是字符串, 不是变量
+        "a; 'python_version' == '3.10'",
+        "a; 'extra' == ''",
+    ];
+    for m in matches {
+        let (_, req) = specification(m).unwrap();
+        assert!(req.evaluate(&env, no_extras), "{}", m);
+    }
+    for m in not_matches {
+        let (_, req) = specification(m).unwrap();
+        assert!(!req.evaluate(&env, no_extras), "{}", m);
+    }
+    assert_eq!(
+        "a; extra == 'extra'"
+            .parse::<RequirementSpecifier>()
+            .unwrap()
+            .to_string(),
+        "a; extra == \"extra\""
+    );
+    let (_, req) = specification("a; extra == 'test' or extra == 'doc'").unwrap();
+    assert!(req.evaluate(&env, &["test"]));
+    assert!(req.evaluate(&env, &["socks", "doc"]));
+    assert!(!req.evaluate(&env, &["socks"]));
+    let (_, req) = specification("a").unwrap();
+    assert!(req.evaluate(&MarkerEnvironment::default(), no_extras));
+}
+
+#[test]
+fn test_specifier_set_prereleases() {
+    let set = |s: &str| s.parse::<SpecifierSet>().unwrap();
+    // (version, specifier set, prereleases, expected)
+    let cases = [
+        ("1.0", "", None, true),
+        ("1.0a1", "", None, false),
+        ("1.0a1", "", Some(true), true),
+        ("2.0a1", ">=1.0", None, false),
+        ("2.0a1", ">=1.0", Some(true), true),
+        ("2.0.dev1", ">=1.0", None, false),
+        ("2.0", ">=1.0", Some(false), true),
+        // 显式包含pre-release
+        ("2.0a1", ">=1.0a1", None, true),
+        ("2.0a1", ">=1.0a1", Some(false), false),
+        ("1.0rc1", "==1.0rc1", None, true),
+        ("1.0rc1", "~=1.0rc1", None, true),
+        ("1.0rc1", "==1.0.*", None, false),
+        // <和>中的pre-release也算
+        ("1.0a1", "<2.0a1", None, true),
+        ("2.0a1", ">1.0rc1", None, true),
+        ("2.0a1", ">=1.0,<3.0a1", None, true),
+        // !=中的pre-release不算
+        ("2.0a1", ">=1.0,!=1.1a1", None, false),
+        ("1.5a1", ">=1.0a1,<2.0", None, true),
+    ];
+    for (version, spec, prereleases, expected) in cases {
+        assert_eq!(
+            set(spec).contains(version, prereleases),
+            expected,
+            "{} {} {:?}",
+            version,
+            spec,
+            prereleases
+        );
+    }
+}
+
+#[test]
+fn test_specifier_set_filter() {
+    let set = |s: &str| s.parse::<SpecifierSet>().unwrap();
+    let versions = ["1.0", "1.5a1", "2.0", "2.1b1", "3.0.dev0", "invalid"];
+    assert_eq!(set(">=1.0").filter(versions, None), vec!["1.0", "2.0"]);
+    assert_eq!(
+        set(">=1.0").filter(versions, Some(true)),
+        vec!["1.0", "1.5a1", "2.0", "2.1b1", "3.0.dev0"]
+    );
+    assert_eq!(set(">=1.5a1,<2").filter(versions, None), vec!["1.5a1"]);
+    assert_eq!(set("").filter(versions, None), vec!["1.0", "2.0"]);
+    // 没有final release满足条件时返回pre-releases
+    assert_eq!(
+        set(">2.0").filter(versions, None),
+        vec!["2.1b1", "3.0.dev0"]
+    );
+    assert_eq!(
+        set(">2.0").filter(versions, Some(false)),
+        Vec::<&str>::new()
+    );
+    assert_eq!(
+        set("(>=1.0, <2.0)").filter(vec!["1.0".to_string(), "1.9".to_string()], None),
+        vec!["1.0".to_string(), "1.9".to_string()]
+    );
+    assert!("!=".parse::<SpecifierSet>().is_err());
+}
+
+#[test]
+fn test_version_pattern() {
+    let spec = |s: &str| s.parse::<VersionSpec>().unwrap();
+    assert_eq!(
+        spec("== 1.0.*"),
+        VersionSpec(
+            Comparison::Equal,
+            VersionPattern::Version {
+                version: "1.0".parse().unwrap(),
+                wildcard: true
+            }
+        )
+    );
+    let VersionSpec(_, VersionPattern::Version { version, wildcard }) = spec("!=v1.0+Ubuntu.1")
+    else {
+        panic!()
+    };
+    assert!(!wildcard);
+    assert_eq!(
+        version.local,
+        Some(vec![
+            LocalVersionPart::LowerStr("ubuntu".to_string()),
+            LocalVersionPart::Num(1)
+        ])
+    );
+    assert_eq!(
+        spec("===foobar").1,
+        VersionPattern::Arbitrary("foobar".to_string())
+    );
+    // 和Specifier.__eq__一致
+    assert_eq!(spec("==1.0"), spec("==1"));
+    assert_eq!(spec(">=1.0.post1"), spec(">=1.post1"));
+    assert_ne!(spec("==1.0.*"), spec("==1.*"));
+    assert_ne!(spec("~=1.0"), spec("~=1.0.0"));
+    assert_ne!(spec("==1.0"), spec("!=1.0"));
+    // 不合法的spec在解析时就报错
+    assert!("~=1".parse::<VersionSpec>().is_err());
+    assert!(">=1.0.*".parse::<VersionSpec>().is_err());
+    assert!(">=1.0+local".parse::<VersionSpec>().is_err());
+    // 用解析好的版本匹配
+    let spec = spec("~=2.2.post3");
+    let versions: Vec<Version> = ["2.2", "2.2.post3", "2.3", "3.0"]
+        .iter()
+        .map(|v| v.parse().unwrap())
+        .collect();
+    assert_eq!(
+        versions.iter().map(|v| spec.matches(v)).collect::<Vec<_>>(),
+        vec![false, true, true, false]
+    );
+}
+
+#[test]
+fn test_display() {
+    for (input, expected) in [
+        ("A", "A"),
+        ("A.B-C_D", "A.B-C_D"),
+        (
+            "name [fred,bar, fred] >= 1.0.PoSt1 , < 2",
+            "name[bar,fred]>=1.0.post1,<2",
+        ),
+        (
+            "name (== v1.0.*, !=1.0.1+Local)",
+            "name==1.0.*,!=1.0.1+local",
+        ),
+        ("name ~=2.2 , ===foo.bar", "name~=2.2,===foo.bar"),
+        (
+            "name [fred] @ http://foo.com ; python_version=='2.7'",
+            "name[fred] @ http://foo.com ; python_version == \"2.7\"",
+        ),
+        ("name@http://foo.com", "name @ http://foo.com"),
+        (
+            "name;'linux' in sys_platform and extra!='a\"b'",
+            "name; \"linux\" in sys_platform and extra != 'a\"b'",
+        ),
+        (
+            "name; (os_name=='a' or os_name=='b') and python_version>'3'",
+            "name; (os_name == \"a\" or os_name == \"b\") and python_version > \"3\"",
+        ),
+        (
+            "name; os_name=='a' or (os_name=='b' and python_version not in '3.1')",
+            "name; os_name == \"a\" or os_name == \"b\" and python_version not in \"3.1\"",
+        ),
+    ] {
+        let req: RequirementSpecifier = input.parse().unwrap();
+        assert_eq!(req.to_string(), expected, "{}", input);
+    }
+    let marker = |s: &str| s.parse::<MarkerExpr>().unwrap();
+    // 右结合的同种操作需要括号
+    for input in [
+        "os_name == \"a\" and (os_name == \"b\" and os_name == \"c\")",
+        "os_name == \"a\" or (os_name == \"b\" or os_name == \"c\")",
+        "(os_name == \"a\" or os_name == \"b\") and (os_name == \"c\" or os_name == \"d\")",
+        "os_name == \"a\" and os_name == \"b\" or os_name == \"c\" and os_name == \"d\"",
+    ] {
+        assert_eq!(marker(input).to_string(), input);
+    }
+    assert_eq!(
+        marker("((os_name=='a') and os_name=='b')").to_string(),
+        "os_name == \"a\" and os_name == \"b\""
+    );
+    assert_eq!(Comparison::CompatibleRelease.to_string(), "~=");
+    assert_eq!(">=1.0".parse::<VersionSpec>().unwrap().to_string(), ">=1.0");
+}
+
+#[test]
+fn test_display_round_trip() {
+    let round_trip = |req: &RequirementSpecifier| {
+        let displayed = req.to_string();
+        assert_eq!(
+            &displayed.parse::<RequirementSpecifier>().unwrap(),
+            req,
+            "{}",
+            displayed
+        );
+    };
+    for input in [
+        "name[quux, strange];python_version<'2.7' and platform_version=='2'",
+        "name>=3,<2",
+        "name@http://foo.com",
+        "name [fred,bar] @ http://foo.com ; python_version=='2.7'",
+        "a[b,a]",
+        "a[b, a, B]",
+        "a; extra == 'extra'",
+        "a; 'python_version' == \"3.10\"",
+        "name; os_name=='a' and (os_name=='b' and os_name=='c') or (extra == 'x' or extra == 'y')",
+        "name===1.0-foo; python_full_version >= '3.8.1'",
+    ] {
+        round_trip(&input.parse().unwrap());
+    }
+    // 宽松模式修复后的结果也能输出为严格模式能解析的文本
+    for line in include_str!("../../fixtures/unrecognized.txt").lines() {
+        if let Ok((req, _)) = RequirementSpecifier::parse_lenient(line) {
+            round_trip(&req);
+        }
+    }
+}
+
+#[test]
+fn test_name_normalization() {
+    for (name, expected) in [
+        ("A.B-C_D", "a-b-c-d"),
+        ("a-b-c-d", "a-b-c-d"),
+        ("Friendly-Bard", "friendly-bard"),
+        ("FRIENDLY-BARD", "friendly-bard"),
+        ("friendly.bard", "friendly-bard"),
+        ("friendly_bard", "friendly-bard"),
+        ("friendly--bard", "friendly-bard"),
+        ("FrIeNdLy-._.-bArD", "friendly-bard"),
+        ("mssql_TLSv1_0", "mssql-tlsv1-0"),
+    ] {
+        assert_eq!(normalize_name(name), expected, "{}", name);
+    }
+    let name: PackageName = "A.B-C_D".parse().unwrap();
+    assert_eq!(name.as_str(), "A.B-C_D");
+    assert_eq!(name.normalized(), "a-b-c-d");
+    assert_eq!(name.to_string(), "A.B-C_D");
+    assert_eq!(name, PackageName::new("a-b-c-d"));
+    assert!("-a".parse::<PackageName>().is_err());
+    assert!("a b".parse::<ExtraName>().is_err());
+    let names: std::collections::HashSet<PackageName> = ["Django", "django", "DJANGO", "flask"]
+        .into_iter()
+        .map(PackageName::new)
+        .collect();
+    assert_eq!(names.len(), 2);
+
+    let req = |s: &str| s.parse::<RequirementSpecifier>().unwrap();
+    assert_eq!(req("A.B-C_D>=1"), req("a-b-c-d >= 1.0"));
+    // extras按规范化的名字比较, 输出时去重
+    let pyodbc = req("pyodbc[mssql_TLSv1_0, MSSQL-tlsv1.0]");
+    assert_eq!(pyodbc.extras[0], pyodbc.extras[1]);
+    assert_eq!(pyodbc.extras[1].as_str(), "MSSQL-tlsv1.0");
+    assert_eq!(pyodbc.to_string(), "pyodbc[mssql_TLSv1_0]");
+    assert_eq!(req("a[Foo_Bar]"), req("a[foo-bar]"));
+}
+
+#[test]
+fn test_marker_evaluate_extra_normalization() {
+    let env = MarkerEnvironment::default();
+    let marker: MarkerExpr = "extra == 'mssql_TLSv1_0'".parse().unwrap();
+    assert!(marker.evaluate(&env, &["mssql-tlsv1-0"]));
+    assert!(marker.evaluate(&env, &["MSSQL.TLSv1.0"]));
+    assert!(!marker.evaluate(&env, &["mssql"]));
+    let marker: MarkerExpr = "'Foo.Bar' != extra".parse().unwrap();
+    assert!(!marker.evaluate(&env, &["foo_bar"]));
+    assert!(marker.evaluate::<&str>(&env, &[]));
+}
+
+#[test]
+fn test_vcs_url() {
+    let url: VcsUrl =
+        "git+ssh://git@github.com/pypa/pip.git@v22.0?x=1#egg=pip&subdirectory=src&sha256=abc"
+            .parse()
+            .unwrap();
+    assert_eq!(
+        url,
+        VcsUrl {
+            vcs: VersionControlSystem::Git,
+            url: "ssh://git@github.com/pypa/pip.git?x=1".to_string(),
+            rev: Some("v22.0".to_string()),
+            egg: Some("pip".to_string()),
+            subdirectory: Some("src".to_string()),
+            params: vec![("sha256".to_string(), "abc".to_string())],
+        }
+    );
+    assert_eq!(
+        url.to_string(),
+        "git+ssh://git@github.com/pypa/pip.git@v22.0?x=1#egg=pip&subdirectory=src&sha256=abc"
+    );
+    // netloc中的'@'不是rev
+    let url: VcsUrl = "hg+https://user@hg.example.com/repo".parse().unwrap();
+    assert_eq!(
+        (url.vcs, url.url.as_str(), url.rev),
+        (
+            VersionControlSystem::Mercurial,
+            "https://user@hg.example.com/repo",
+            None
+        )
+    );
+    for s in [
+        "svn+svn://svn.example.com/repo/trunk@2019#egg=MyProject",
+        "bzr+lp:MyProject#subdirectory=sub&egg=MyProject",
+    ] {
+        let url: VcsUrl = s.parse().unwrap();
+        assert_eq!(url.to_string().parse::<VcsUrl>().unwrap(), url);
+    }
+    assert_eq!(
+        "BZR+lp:MyProject".parse::<VcsUrl>().unwrap().vcs,
+        VersionControlSystem::Bazaar
+    );
+    assert!("https://github.com/pypa/pip.git".parse::<VcsUrl>().is_err());
+    assert!("foo+https://example.com/repo".parse::<VcsUrl>().is_err());
+    assert!("git+https://github.com/pypa/pip.git@"
+        .parse::<VcsUrl>()
+        .is_err());
+
+    let req: RequirementSpecifier = "pip @ git+https://github.com/pypa/pip.git@22.0"
+        .parse()
+        .unwrap();
+    assert_eq!(req.vcs_url().unwrap().rev, Some("22.0".to_string()));
+    let req: RequirementSpecifier = "pip @ https://example.com/pip-22.0.tar.gz".parse().unwrap();
+    assert_eq!(req.vcs_url(), None);
+}
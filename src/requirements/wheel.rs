@@ -0,0 +1,395 @@
+//! wheel文件名和兼容性tag, 参考packaging中的utils.py和tags.py
+//! TargetTags按优先级从高到低生成目标环境支持的tag, 用来离线判断一个wheel能否安装
+use std::{fmt::Display, str::FromStr};
+
+use super::{PackageName, Version};
+use crate::{
+    error::{parse_complete, ParseError},
+    parser::wheel_filename::wheel_filename,
+};
+
+// 见packaging中tags.py的Tag, 各部分都是小写
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone)]
+pub struct Tag {
+    pub interpreter: String,
+    pub abi: String,
+    pub platform: String,
+}
+
+impl Tag {
+    pub fn new(interpreter: &str, abi: &str, platform: &str) -> Self {
+        Self {
+            interpreter: interpreter.to_ascii_lowercase(),
+            abi: abi.to_ascii_lowercase(),
+            platform: platform.to_ascii_lowercase(),
+        }
+    }
+}
+
+impl Display for Tag {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}-{}-{}", self.interpreter, self.abi, self.platform)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct WheelFilename {
+    pub name: PackageName,
+    pub version: Version,
+    pub build: Option<(u64, String)>,
+    // 展开之后的tag, 如 py2.py3-none-any 展开为 py2-none-any 和 py3-none-any
+    pub tags: Vec<Tag>,
+}
+
+impl FromStr for WheelFilename {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_complete(s, wheel_filename, "end of input")
+    }
+}
+
+// 目标环境的平台, 带版本的平台展开为所有兼容的旧版本
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Platform {
+    // glibc的(major, minor), 如 (2, 31); 见packaging中_manylinux.py的platform_tags
+    Manylinux { glibc: (u32, u32), arch: String },
+    // musl的(major, minor), 如 (1, 2); 见packaging中_musllinux.py的platform_tags
+    Musllinux { musl: (u32, u32), arch: String },
+    // macOS的(major, minor), 如 (14, 0); 见packaging中tags.py的mac_platforms
+    Macos { version: (u32, u32), arch: String },
+    // 不展开, 如 win_amd64, linux_x86_64
+    Other(String),
+}
+
+// manylinux1, manylinux2010和manylinux2014对应的glibc版本
+static LEGACY_MANYLINUX: [((u32, u32), &str); 3] = [
+    ((2, 17), "manylinux2014"),
+    ((2, 12), "manylinux2010"),
+    ((2, 5), "manylinux1"),
+];
+
+// 见packaging中tags.py的_mac_binary_formats
+fn mac_binary_formats(version: (u32, u32), arch: &str) -> Vec<&str> {
+    let mut formats = vec![arch];
+    match arch {
+        "x86_64" if version < (10, 4) => return vec![],
+        "x86_64" => formats.extend(["intel", "fat64", "fat32"]),
+        "i386" if version < (10, 4) => return vec![],
+        "i386" => formats.extend(["intel", "fat32", "fat"]),
+        "ppc64" if !((10, 4)..=(10, 5)).contains(&version) => return vec![],
+        "ppc64" => formats.push("fat64"),
+        "ppc" if version > (10, 6) => return vec![],
+        "ppc" => formats.extend(["fat32", "fat"]),
+        _ => {}
+    }
+    if matches!(arch, "arm64" | "x86_64") {
+        formats.push("universal2");
+    }
+    if matches!(arch, "x86_64" | "i386" | "ppc64" | "ppc" | "intel") {
+        formats.push("universal");
+    }
+    formats
+}
+
+fn mac_platforms(version: (u32, u32), arch: &str) -> Vec<String> {
+    let mut platforms = vec![];
+    let mut push = |major: u32, minor: u32, formats: Vec<&str>| {
+        for format in formats {
+            platforms.push(format!("macosx_{}_{}_{}", major, minor, format));
+        }
+    };
+    if ((10, 0)..(11, 0)).contains(&version) {
+        // macOS 11之前每年的版本增加minor
+        for minor in (0..=version.1).rev() {
+            push(10, minor, mac_binary_formats((10, minor), arch));
+        }
+    }
+    if version >= (11, 0) {
+        // 从macOS 11开始每年的版本增加major
+        for major in (11..=version.0).rev() {
+            push(major, 0, mac_binary_formats((major, 0), arch));
+        }
+        // x86_64兼容之前的版本; arm64从11.0开始支持, 之前的版本只有universal2
+        for minor in (4..=16).rev() {
+            match arch {
+                "x86_64" => push(10, minor, mac_binary_formats((10, minor), arch)),
+                _ => push(10, minor, vec!["universal2"]),
+            }
+        }
+    }
+    platforms
+}
+
+impl Platform {
+    // 按优先级从高到低
+    pub fn tags(&self) -> Vec<String> {
+        match self {
+            Self::Manylinux { glibc, arch } => {
+                // x86_64和i686最早支持glibc 2.5, 其他架构最早支持2.17
+                let oldest = match arch.as_str() {
+                    "x86_64" | "i686" => 5,
+                    _ => 17,
+                };
+                let mut tags = vec![];
+                for minor in (oldest..=glibc.1).rev() {
+                    tags.push(format!("manylinux_{}_{}_{}", glibc.0, minor, arch));
+                    if let Some((_, legacy)) =
+                        LEGACY_MANYLINUX.iter().find(|(v, _)| *v == (2, minor))
+                    {
+                        tags.push(format!("{}_{}", legacy, arch));
+                    }
+                }
+                tags.push(format!("linux_{}", arch));
+                tags
+            }
+            Self::Musllinux { musl, arch } => {
+                let mut tags: Vec<String> = (0..=musl.1)
+                    .rev()
+                    .map(|minor| format!("musllinux_{}_{}_{}", musl.0, minor, arch))
+                    .collect();
+                tags.push(format!("linux_{}", arch));
+                tags
+            }
+            Self::Macos { version, arch } => mac_platforms(*version, arch),
+            Self::Other(platform) => vec![platform.to_ascii_lowercase()],
+        }
+    }
+}
+
+// 目标解释器, 如 implementation="cp", python_version=(3, 11), abis=["cp311"]
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct TargetTags {
+    // 解释器的缩写: cp, pp, ...
+    pub implementation: String,
+    pub python_version: (u32, u32),
+    pub abis: Vec<String>,
+    pub platform: Platform,
+}
+
+// 见packaging中tags.py的_py_interpreter_range: py311, py3, py310, ..., py30
+fn py_interpreter_range(python_version: (u32, u32)) -> Vec<String> {
+    let (major, minor) = python_version;
+    let mut versions = vec![format!("py{}{}", major, minor), format!("py{}", major)];
+    versions.extend((0..minor).rev().map(|m| format!("py{}{}", major, m)));
+    versions
+}
+
+impl TargetTags {
+    pub fn interpreter(&self) -> String {
+        let (major, minor) = self.python_version;
+        format!("{}{}{}", self.implementation, major, minor)
+    }
+
+    // 和packaging中tags.py的sys_tags顺序相同: cpython_tags或generic_tags, 然后是compatible_tags
+    pub fn tags(&self) -> Vec<Tag> {
+        let interpreter = self.interpreter();
+        let platforms = self.platform.tags();
+        let mut tags = vec![];
+        let push_all = |tags: &mut Vec<Tag>, interpreter: &str, abi: &str| {
+            for platform in &platforms {
+                tags.push(Tag::new(interpreter, abi, platform));
+            }
+        };
+        // abi3和none单独处理
+        let abis = self
+            .abis
+            .iter()
+            .filter(|abi| !matches!(abi.as_str(), "abi3" | "none"));
+        if self.implementation == "cp" {
+            let abi3 = self.python_version >= (3, 2);
+            for abi in abis {
+                push_all(&mut tags, &interpreter, abi);
+            }
+            if abi3 {
+                push_all(&mut tags, &interpreter, "abi3");
+            }
+            push_all(&mut tags, &interpreter, "none");
+            if abi3 {
+                let (major, minor) = self.python_version;
+                for minor in (2..minor).rev() {
+                    push_all(&mut tags, &format!("cp{}{}", major, minor), "abi3");
+                }
+            }
+        } else {
+            for abi in abis {
+                push_all(&mut tags, &interpreter, abi);
+            }
+            push_all(&mut tags, &interpreter, "none");
+        }
+        for version in py_interpreter_range(self.python_version) {
+            push_all(&mut tags, &version, "none");
+        }
+        tags.push(Tag::new(&interpreter, "none", "any"));
+        for version in py_interpreter_range(self.python_version) {
+            tags.push(Tag::new(&version, "none", "any"));
+        }
+        tags
+    }
+
+    // wheel中优先级最高的tag在tags()中的位置, 越小越优先; 不兼容时为None
+    pub fn priority(&self, wheel: &WheelFilename) -> Option<usize> {
+        let tags = self.tags();
+        wheel
+            .tags
+            .iter()
+            .filter_map(|tag| tags.iter().position(|t| t == tag))
+            .min()
+    }
+
+    pub fn supports(&self, wheel: &WheelFilename) -> bool {
+        self.priority(wheel).is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Platform, Tag, TargetTags, WheelFilename};
+    use crate::requirements::PackageName;
+
+    fn wheel(s: &str) -> WheelFilename {
+        s.parse().unwrap()
+    }
+
+    fn target(platform: Platform) -> TargetTags {
+        TargetTags {
+            implementation: "cp".to_string(),
+            python_version: (3, 11),
+            abis: vec!["cp311".to_string()],
+            platform,
+        }
+    }
+
+    #[test]
+    fn test_wheel_filename() {
+        let w = wheel("Foo_Bar-1.0.post1-2local-py2.py3-none-any.whl");
+        assert_eq!(w.name, PackageName::new("foo-bar"));
+        assert_eq!(w.version, "1.0.post1".parse().unwrap());
+        assert_eq!(w.build, Some((2, "local".to_string())));
+        assert_eq!(
+            w.tags,
+            [
+                Tag::new("py2", "none", "any"),
+                Tag::new("py3", "none", "any")
+            ]
+        );
+
+        let w = wheel("numpy-1.26.4-cp311-cp311-manylinux_2_17_x86_64.manylinux2014_x86_64.whl");
+        assert_eq!(w.build, None);
+        assert_eq!(w.tags.len(), 2);
+        assert_eq!(w.tags[1].to_string(), "cp311-cp311-manylinux2014_x86_64");
+
+        for (s, message) in [
+            (
+                "foo-1.0-py3-none-any.zip",
+                "expected '.whl', found end of input",
+            ),
+            (
+                "foo-1.0-py3-any.whl",
+                "expected name-version[-build]-python-abi-platform.whl, found 'f'",
+            ),
+            (
+                "foo-1.0-x-py3-none-any.whl",
+                "expected build tag, found 'x'",
+            ),
+            ("foo-one-py3-none-any.whl", "expected version, found 'o'"),
+            (
+                "foo!-1.0-py3-none-any.whl",
+                "expected project name, found 'f'",
+            ),
+        ] {
+            assert_eq!(s.parse::<WheelFilename>().unwrap_err().message, message);
+        }
+    }
+
+    #[test]
+    fn test_platform_tags() {
+        let tags = Platform::Manylinux {
+            glibc: (2, 17),
+            arch: "x86_64".to_string(),
+        }
+        .tags();
+        assert_eq!(tags[..2], ["manylinux_2_17_x86_64", "manylinux2014_x86_64"]);
+        assert!(tags.contains(&"manylinux1_x86_64".to_string()));
+        assert_eq!(tags.last().unwrap(), "linux_x86_64");
+        let tags = Platform::Manylinux {
+            glibc: (2, 28),
+            arch: "aarch64".to_string(),
+        }
+        .tags();
+        assert_eq!(tags.len(), 12 + 1 + 1);
+        assert_eq!(
+            Platform::Musllinux {
+                musl: (1, 2),
+                arch: "x86_64".to_string()
+            }
+            .tags(),
+            [
+                "musllinux_1_2_x86_64",
+                "musllinux_1_1_x86_64",
+                "musllinux_1_0_x86_64",
+                "linux_x86_64"
+            ]
+        );
+        let tags = Platform::Macos {
+            version: (12, 0),
+            arch: "arm64".to_string(),
+        }
+        .tags();
+        assert_eq!(
+            tags[..4],
+            [
+                "macosx_12_0_arm64",
+                "macosx_12_0_universal2",
+                "macosx_11_0_arm64",
+                "macosx_11_0_universal2"
+            ]
+        );
+        assert_eq!(tags[4], "macosx_10_16_universal2");
+        let tags = Platform::Macos {
+            version: (10, 9),
+            arch: "x86_64".to_string(),
+        }
+        .tags();
+        assert_eq!(tags[0], "macosx_10_9_x86_64");
+        assert!(tags.contains(&"macosx_10_4_universal".to_string()));
+        assert!(!tags.iter().any(|t| t.starts_with("macosx_10_3")));
+    }
+
+    #[test]
+    fn test_target_tags() {
+        let linux = target(Platform::Manylinux {
+            glibc: (2, 31),
+            arch: "x86_64".to_string(),
+        });
+        let tags = linux.tags();
+        assert_eq!(tags[0].to_string(), "cp311-cp311-manylinux_2_31_x86_64");
+        assert_eq!(tags.last().unwrap().to_string(), "py30-none-any");
+        for (filename, supported) in [
+            (
+                "numpy-1.26.4-cp311-cp311-manylinux_2_17_x86_64.manylinux2014_x86_64.whl",
+                true,
+            ),
+            (
+                "cryptography-42.0.5-cp39-abi3-manylinux_2_28_x86_64.whl",
+                true,
+            ),
+            ("six-1.16.0-py2.py3-none-any.whl", true),
+            ("numpy-1.26.4-cp312-cp312-manylinux_2_17_x86_64.whl", false),
+            ("numpy-1.26.4-cp311-cp311-musllinux_1_1_x86_64.whl", false),
+            ("numpy-1.26.4-cp311-cp311-manylinux_2_34_x86_64.whl", false),
+            ("numpy-1.26.4-cp311-cp311-win_amd64.whl", false),
+        ] {
+            assert_eq!(linux.supports(&wheel(filename)), supported, "{}", filename);
+        }
+        // 平台相关的wheel优先于纯python的wheel
+        assert!(
+            linux.priority(&wheel("foo-1.0-cp311-cp311-linux_x86_64.whl"))
+                < linux.priority(&wheel("foo-1.0-py3-none-any.whl"))
+        );
+
+        let windows = target(Platform::Other("win_amd64".to_string()));
+        assert!(windows.supports(&wheel("numpy-1.26.4-cp311-cp311-win_amd64.whl")));
+        assert_eq!(windows.tags().len(), 3 + 9 + 13 + 1 + 13);
+    }
+}